@@ -0,0 +1,370 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    str::FromStr,
+};
+
+use csv::{ReaderBuilder, StringRecord};
+
+/// One observation: the `N` inputs, the `M` outputs observed for them, and how much each output
+/// counts towards the fit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record<const N: usize, const M: usize = 1> {
+    pub x: [f64; N],
+    pub y: [f64; M],
+    /// Weight of each output in the loss, the inverse of its variance when its uncertainty is
+    /// known
+    pub weight: [f64; M],
+}
+
+/// Column of a CSV file, given either by its header or by its position counting from 0
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    /// Numbers are taken as positions and anything else as a header
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("column must not be empty".into());
+        }
+        Ok(s.parse()
+            .map(Column::Index)
+            .unwrap_or_else(|_| Column::Name(s.into())))
+    }
+}
+
+/// Parse a single ASCII character, as the CSV reader's delimiters and quotes must be
+pub fn parse_byte(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        [byte] if byte.is_ascii() => Ok(*byte),
+        _ if s == "\\t" => Ok(b'\t'),
+        _ => Err(format!("`{s}` is not a single ASCII character")),
+    }
+}
+
+/// How to read records out of a CSV file
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    /// Columns holding each input
+    pub x: Vec<Column>,
+    /// Columns holding each output; if empty, those just after the inputs, assuming the inputs
+    /// come first
+    pub y: Vec<Column>,
+    /// Column holding the weight of each observation, or one column for each output
+    pub weight: Vec<Column>,
+    /// Column holding the standard deviation of each observation, or one column for each
+    /// output, weighting it by `1/σ²`
+    pub sigma: Vec<Column>,
+    pub delimiter: u8,
+    pub quote: u8,
+    /// Lines starting with this character are skipped
+    pub comment: Option<u8>,
+    /// Whether the first row names the columns rather than holding data
+    pub headers: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            x: vec![Column::Index(0)],
+            y: Vec::new(),
+            weight: Vec::new(),
+            sigma: Vec::new(),
+            delimiter: b',',
+            quote: b'"',
+            comment: None,
+            headers: true,
+        }
+    }
+}
+
+/// Read the records of the CSV file at `path`, or of standard input if `path` is `-`
+pub fn load<const N: usize, const M: usize>(
+    path: &Path,
+    options: &CsvOptions,
+) -> Result<Vec<Record<N, M>>, Box<dyn Error>> {
+    if path == Path::new("-") {
+        read(io::stdin().lock(), options)
+    } else {
+        let file =
+            File::open(path).map_err(|err| format!("cannot open {}: {err}", path.display()))?;
+        read(file, options)
+    }
+}
+
+/// Read records out of CSV data, taking x and y from the columns chosen in `options`, which must
+/// choose a column for each of the `N` inputs and, if any, each of the `M` outputs
+pub fn read<const N: usize, const M: usize>(
+    source: impl Read,
+    options: &CsvOptions,
+) -> Result<Vec<Record<N, M>>, Box<dyn Error>> {
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    if options.x.len() != N {
+        return Err(format!(
+            "the model takes {N} input{}, but {} input columns were given",
+            plural(N),
+            options.x.len()
+        )
+        .into());
+    }
+    if !(options.y.is_empty() || options.y.len() == M) {
+        return Err(format!(
+            "the model has {M} output{}, but {} output columns were given",
+            plural(M),
+            options.y.len()
+        )
+        .into());
+    }
+    let (weights, sigma) = match (&options.weight[..], &options.sigma[..]) {
+        ([], sigmas) => (sigmas, true),
+        (weights, []) => (weights, false),
+        _ => return Err("give either weights or sigmas, not both".into()),
+    };
+    if !(weights.len() <= 1 || weights.len() == M) {
+        return Err(format!(
+            "give one weight or sigma column, or one for each of the {M} outputs, not {}",
+            weights.len()
+        )
+        .into());
+    }
+    let mut reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .quote(options.quote)
+        .comment(options.comment)
+        .has_headers(options.headers)
+        .flexible(true)
+        .from_reader(source);
+    let headers = if options.headers {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+    let x = options
+        .x
+        .iter()
+        .map(|column| position(column, headers.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let y = if options.y.is_empty() {
+        (N..N + M).collect()
+    } else {
+        options
+            .y
+            .iter()
+            .map(|column| position(column, headers.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let weights = weights
+        .iter()
+        .map(|column| position(column, headers.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut data = Vec::new();
+    for row in reader.records() {
+        let row = row?;
+        let line = row.position().map_or(0, |position| position.line());
+        let field = |index: usize, valid: fn(f64) -> bool, expected: &str| {
+            let column = describe(index, headers.as_ref());
+            let value = row
+                .get(index)
+                .ok_or_else(|| format!("line {line}: missing column {column}"))?;
+            match value.trim().parse() {
+                Ok(number) if valid(number) => Ok(number),
+                _ => Err(format!(
+                    "line {line}, column {column}: `{value}` is not {expected}"
+                )),
+            }
+        };
+        let mut weight = [1.0; M];
+        for (i, value) in weight.iter_mut().enumerate() {
+            let Some(index) = weights.get(i).or(weights.first()) else {
+                break;
+            };
+            *value = if sigma {
+                let sigma = field(*index, |s| s > 0.0 && s.is_finite(), "a positive sigma")?;
+                1.0 / (sigma * sigma)
+            } else {
+                field(*index, |w| w >= 0.0 && w.is_finite(), "a valid weight")?
+            };
+        }
+        let mut input = [0.0; N];
+        for (value, index) in input.iter_mut().zip(&x) {
+            *value = field(*index, f64::is_finite, "a finite number")?;
+        }
+        let mut output = [0.0; M];
+        for (value, index) in output.iter_mut().zip(&y) {
+            *value = field(*index, f64::is_finite, "a finite number")?;
+        }
+        data.push(Record {
+            x: input,
+            y: output,
+            weight,
+        });
+    }
+    // without outputs the inputs are only there to predict at, with nothing to fit
+    if M > 0
+        && data
+            .iter()
+            .flat_map(|record| record.weight)
+            .all(|weight| weight == 0.0)
+    {
+        return Err("no data to fit".into());
+    }
+    Ok(data)
+}
+
+/// Position of `column` among the fields of each row
+fn position(column: &Column, headers: Option<&StringRecord>) -> Result<usize, Box<dyn Error>> {
+    match (column, headers) {
+        (Column::Index(index), _) => Ok(*index),
+        (Column::Name(name), Some(headers)) => headers
+            .iter()
+            .position(|header| header.trim() == name)
+            .ok_or_else(|| {
+                let available = headers.iter().collect::<Vec<_>>().join(", ");
+                format!("no column named `{name}`; the columns are {available}").into()
+            }),
+        (Column::Name(name), None) => {
+            Err(format!("column `{name}` can't be found by name in a file without headers").into())
+        }
+    }
+}
+
+/// Column at `index`, with its header if it has one, for error messages
+fn describe(index: usize, headers: Option<&StringRecord>) -> String {
+    match headers.and_then(|headers| headers.get(index)) {
+        Some(header) => format!("{index} (`{header}`)"),
+        None => index.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, Column, CsvOptions, Record};
+
+    #[test]
+    fn selects_columns() {
+        let source = "# measured\nt;noise;\"signal\"\n1;0.5;2\n# skipped\n2;0.25;4\n";
+        let options = CsvOptions {
+            x: vec!["t".parse().unwrap()],
+            y: vec!["signal".parse().unwrap()],
+            delimiter: b';',
+            comment: Some(b'#'),
+            ..CsvOptions::default()
+        };
+        let data = read(source.as_bytes(), &options).unwrap();
+        let expected = [(1.0, 2.0), (2.0, 4.0)].map(|(x, y)| Record {
+            x: [x],
+            y: [y],
+            weight: [1.0],
+        });
+        assert_eq!(data, expected);
+
+        let options = CsvOptions {
+            x: vec![Column::Index(1)],
+            y: vec![Column::Index(0)],
+            headers: false,
+            ..CsvOptions::default()
+        };
+        let data = read("1,2,0.5\n3,4,2\n".as_bytes(), &options).unwrap();
+        let expected = [(2.0, 1.0), (4.0, 3.0)].map(|(x, y)| Record {
+            x: [x],
+            y: [y],
+            weight: [1.0],
+        });
+        assert_eq!(data, expected);
+
+        let options = CsvOptions {
+            sigma: vec![Column::Index(2)],
+            ..options
+        };
+        let data = read::<1, 1>("1,2,0.5\n3,4,2\n".as_bytes(), &options).unwrap();
+        let weights = data.iter().map(|record| record.weight).collect::<Vec<_>>();
+        assert_eq!(weights, [[4.0], [0.25]]);
+
+        let options = CsvOptions {
+            x: vec![Column::Index(2), Column::Index(0)],
+            y: vec![Column::Index(1)],
+            headers: false,
+            ..CsvOptions::default()
+        };
+        let data = read("1,2,3\n4,5,6\n".as_bytes(), &options).unwrap();
+        assert_eq!(data[1].x, [6.0, 4.0]);
+        assert_eq!(data[1].y, [5.0]);
+        assert!(read::<1, 1>("1,2,3\n".as_bytes(), &options).is_err());
+    }
+
+    #[test]
+    fn reads_outputs() {
+        // outputs default to the columns after the inputs, each with its own sigma
+        let source = "t,x,y,sx,sy\n0,1,2,0.5,0.25\n";
+        let options = CsvOptions {
+            sigma: vec!["sx".parse().unwrap(), "sy".parse().unwrap()],
+            ..CsvOptions::default()
+        };
+        let data = read(source.as_bytes(), &options).unwrap();
+        assert_eq!(
+            data,
+            [Record {
+                x: [0.0],
+                y: [1.0, 2.0],
+                weight: [4.0, 16.0],
+            }]
+        );
+        assert!(read::<1, 3>(source.as_bytes(), &options).is_err());
+
+        // inputs alone, to predict at
+        let options = CsvOptions {
+            x: vec![Column::Index(0), Column::Index(1)],
+            ..CsvOptions::default()
+        };
+        let data = read::<2, 0>("a,b\n1,2\n".as_bytes(), &options).unwrap();
+        assert_eq!(data[0].x, [1.0, 2.0]);
+    }
+
+    #[test]
+    fn reports_bad_values() {
+        let source = "x,y\n1,2\n3,four\n";
+        let err = read::<1, 1>(source.as_bytes(), &CsvOptions::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 1 (`y`): `four` is not a finite number"
+        );
+        for (source, message) in [
+            (
+                "x,y\nNaN,2\n",
+                "line 2, column 0 (`x`): `NaN` is not a finite number",
+            ),
+            (
+                "x,y\n1,inf\n",
+                "line 2, column 1 (`y`): `inf` is not a finite number",
+            ),
+        ] {
+            let err = read::<1, 1>(source.as_bytes(), &CsvOptions::default()).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+
+        let options = CsvOptions {
+            y: vec!["z".parse().unwrap()],
+            ..CsvOptions::default()
+        };
+        let err = read::<1, 1>(source.as_bytes(), &options).unwrap_err();
+        assert_eq!(err.to_string(), "no column named `z`; the columns are x, y");
+
+        let options = CsvOptions {
+            sigma: vec![Column::Index(0)],
+            ..CsvOptions::default()
+        };
+        let err = read::<1, 1>("x,y\n0,1\n".as_bytes(), &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 0 (`x`): `0` is not a positive sigma"
+        );
+    }
+}
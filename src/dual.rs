@@ -0,0 +1,338 @@
+use std::{
+    array,
+    fmt::Debug,
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub},
+};
+
+/// Number type that regressors and their functions can be written over, so that the same code
+/// computes either plain values (`f64`) or values with exact derivatives ([`Dual`])
+pub trait Scalar:
+    Copy
+    + Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Add<f64, Output = Self>
+    + Sub<f64, Output = Self>
+    + Mul<f64, Output = Self>
+    + Div<f64, Output = Self>
+    + AddAssign<f64>
+{
+    /// Number of variables this scalar tracks derivatives with respect to
+    const VARIABLES: usize;
+
+    fn constant(value: f64) -> Self;
+    /// The `index`th variable being differentiated with respect to, or a constant if this scalar
+    /// doesn't track that many variables
+    fn variable(value: f64, index: usize) -> Self;
+    fn value(self) -> f64;
+    /// Partial derivative with respect to the `index`th variable
+    fn derivative(self, index: usize) -> f64;
+
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn tanh(self) -> Self;
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn powf(self, n: Self) -> Self;
+}
+
+impl Scalar for f64 {
+    const VARIABLES: usize = 0;
+
+    fn constant(value: f64) -> Self {
+        value
+    }
+
+    fn variable(value: f64, _index: usize) -> Self {
+        value
+    }
+
+    fn value(self) -> f64 {
+        self
+    }
+
+    fn derivative(self, _index: usize) -> f64 {
+        0.0
+    }
+
+    fn exp(self) -> Self {
+        f64::exp(self)
+    }
+
+    fn ln(self) -> Self {
+        f64::ln(self)
+    }
+
+    fn sin(self) -> Self {
+        f64::sin(self)
+    }
+
+    fn cos(self) -> Self {
+        f64::cos(self)
+    }
+
+    fn tan(self) -> Self {
+        f64::tan(self)
+    }
+
+    fn tanh(self) -> Self {
+        f64::tanh(self)
+    }
+
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    fn powf(self, n: Self) -> Self {
+        f64::powf(self, n)
+    }
+}
+
+/// Forward-mode dual number: a value along with its partial derivatives with respect to `N`
+/// variables
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dual<const N: usize> {
+    pub value: f64,
+    pub derivatives: [f64; N],
+}
+
+impl<const N: usize> Dual<N> {
+    /// The `index`th of the `N` variables being differentiated with respect to
+    pub fn variable(value: f64, index: usize) -> Self {
+        Self {
+            value,
+            derivatives: array::from_fn(|i| if i == index { 1.0 } else { 0.0 }),
+        }
+    }
+
+    /// Seed each value as the variable of the same index
+    pub fn variables<const M: usize>(values: [f64; M]) -> [Self; M] {
+        array::from_fn(|i| Self::variable(values[i], i))
+    }
+
+    /// Apply a function with derivative `derivative` at `self.value` using the chain rule
+    fn chain(self, value: f64, derivative: f64) -> Self {
+        Self {
+            value,
+            derivatives: self.derivatives.map(|d| d * derivative),
+        }
+    }
+}
+
+impl<const N: usize> Add for Dual<N> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            value: self.value + rhs.value,
+            derivatives: array::from_fn(|i| self.derivatives[i] + rhs.derivatives[i]),
+        }
+    }
+}
+
+impl<const N: usize> Sub for Dual<N> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            value: self.value - rhs.value,
+            derivatives: array::from_fn(|i| self.derivatives[i] - rhs.derivatives[i]),
+        }
+    }
+}
+
+impl<const N: usize> Mul for Dual<N> {
+    type Output = Self;
+
+    // product rule
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            value: self.value * rhs.value,
+            derivatives: array::from_fn(|i| {
+                self.derivatives[i] * rhs.value + self.value * rhs.derivatives[i]
+            }),
+        }
+    }
+}
+
+impl<const N: usize> Div for Dual<N> {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let denominator = rhs.value * rhs.value;
+        Self {
+            value: self.value / rhs.value,
+            derivatives: array::from_fn(|i| {
+                (self.derivatives[i] * rhs.value - self.value * rhs.derivatives[i]) / denominator
+            }),
+        }
+    }
+}
+
+impl<const N: usize> Neg for Dual<N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            value: -self.value,
+            derivatives: self.derivatives.map(|d| -d),
+        }
+    }
+}
+
+impl<const N: usize> Add<f64> for Dual<N> {
+    type Output = Self;
+
+    fn add(self, rhs: f64) -> Self {
+        Self {
+            value: self.value + rhs,
+            ..self
+        }
+    }
+}
+
+impl<const N: usize> Sub<f64> for Dual<N> {
+    type Output = Self;
+
+    fn sub(self, rhs: f64) -> Self {
+        Self {
+            value: self.value - rhs,
+            ..self
+        }
+    }
+}
+
+impl<const N: usize> Mul<f64> for Dual<N> {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Self {
+            value: self.value * rhs,
+            derivatives: self.derivatives.map(|d| d * rhs),
+        }
+    }
+}
+
+impl<const N: usize> Div<f64> for Dual<N> {
+    type Output = Self;
+
+    fn div(self, rhs: f64) -> Self {
+        Self {
+            value: self.value / rhs,
+            derivatives: self.derivatives.map(|d| d / rhs),
+        }
+    }
+}
+
+impl<const N: usize> AddAssign<f64> for Dual<N> {
+    fn add_assign(&mut self, rhs: f64) {
+        self.value += rhs;
+    }
+}
+
+impl<const N: usize> Scalar for Dual<N> {
+    const VARIABLES: usize = N;
+
+    fn constant(value: f64) -> Self {
+        Self {
+            value,
+            derivatives: [0.0; N],
+        }
+    }
+
+    fn variable(value: f64, index: usize) -> Self {
+        Dual::variable(value, index)
+    }
+
+    fn value(self) -> f64 {
+        self.value
+    }
+
+    fn derivative(self, index: usize) -> f64 {
+        self.derivatives[index]
+    }
+
+    fn exp(self) -> Self {
+        let value = self.value.exp();
+        self.chain(value, value)
+    }
+
+    fn ln(self) -> Self {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    fn sin(self) -> Self {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    fn cos(self) -> Self {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    fn tan(self) -> Self {
+        let value = self.value.tan();
+        self.chain(value, 1.0 + value * value)
+    }
+
+    fn tanh(self) -> Self {
+        let value = self.value.tanh();
+        self.chain(value, 1.0 - value * value)
+    }
+
+    fn abs(self) -> Self {
+        self.chain(self.value.abs(), self.value.signum())
+    }
+
+    fn sqrt(self) -> Self {
+        let value = self.value.sqrt();
+        self.chain(value, 0.5 / value)
+    }
+
+    fn powf(self, n: Self) -> Self {
+        let value = self.value.powf(n.value);
+        // d(a^b) = b a^(b - 1) da + a^b ln(a) db, skipping the second term when b is constant
+        // so that negative bases with integer exponents stay finite
+        let base_derivative = n.value * self.value.powf(n.value - 1.0);
+        let exponent_derivative = if n.derivatives.iter().all(|d| *d == 0.0) {
+            0.0
+        } else {
+            value * self.value.ln()
+        };
+        Self {
+            value,
+            derivatives: array::from_fn(|i| {
+                base_derivative * self.derivatives[i] + exponent_derivative * n.derivatives[i]
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dual, Scalar};
+
+    #[test]
+    fn derivatives() {
+        let [a, b] = Dual::<2>::variables([3.0, 0.5]);
+        let x = 2.0;
+        let y = a * (-b * x).exp() + (a * b).sin() / b.sqrt() - a.powf(b);
+        let expected_a =
+            (-0.5 * x).exp() + 0.5 * (1.5f64).cos() / 0.5f64.sqrt() - 0.5 * 3.0f64.powf(-0.5);
+        let expected_b = -x * 3.0 * (-0.5 * x).exp()
+            + (3.0 * (1.5f64).cos() * 0.5f64.sqrt() - (1.5f64).sin() * 0.5 / 0.5f64.sqrt()) / 0.5
+            - 3.0f64.powf(0.5) * 3.0f64.ln();
+        assert!((y.derivatives[0] - expected_a).abs() < 1e-12);
+        assert!((y.derivatives[1] - expected_b).abs() < 1e-12);
+    }
+}
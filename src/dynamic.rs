@@ -0,0 +1,616 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+#[cfg(feature = "nightly")]
+use std::array;
+
+#[cfg(feature = "nightly")]
+use crate::GradientDescent;
+use crate::{
+    apply_nudge,
+    data::Record,
+    dual::{Dual, Scalar},
+    evaluate_polynomial,
+    expression::{Expression, ParseError},
+    losses::Loss,
+    output_weights, polynomial_derivatives,
+    solvers::LevenbergMarquardt,
+    statistics::DynamicCovariance,
+    weigh_residuals, weight_sum, weighted_loss, weighted_squared_error, write_formula,
+    write_polynomial,
+};
+
+/// Model whose dimensions are only known at runtime, the counterpart of
+/// [`GradientDescent`](crate::GradientDescent) that builds on stable Rust and can be boxed as a
+/// trait object. Inputs, outputs and parameters are slices of the lengths the model reports
+pub trait Regressor: Display {
+    fn input_count(&self) -> usize;
+    fn param_count(&self) -> usize;
+    fn output_count(&self) -> usize;
+
+    /// Outputs at `input`, with the `nudge.0`th parameter increased by `nudge.1` if given
+    fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64]) -> Vec<f64>;
+    /// Current parameter values, in the same order as the adjustments passed to `descend`
+    fn parameters(&self) -> Vec<f64>;
+    /// Overwrite every parameter, in the order of `parameters`, failing if there aren't as many
+    /// values as parameters
+    fn set_parameters(&mut self, parameters: &[f64]) -> Result<(), Box<dyn Error>>;
+    fn descend(&mut self, adjustments: &[f64]);
+
+    /// Name of each parameter, in the order of `parameters`. Defaults to `p0`, `p1`, ...
+    fn parameter_names(&self) -> Vec<String> {
+        (0..self.param_count()).map(|i| format!("p{i}")).collect()
+    }
+
+    /// Partial derivatives of each output with respect to each parameter at `input`. Defaults to
+    /// a finite difference estimate that nudges each parameter by `epsilon`
+    fn jacobian(&self, input: &[f64], epsilon: f64) -> Vec<Vec<f64>> {
+        let base = self.predict(None, input);
+        let nudged = (0..self.param_count())
+            .map(|nudge| self.predict(Some((nudge, epsilon)), input))
+            .collect::<Vec<_>>();
+        (0..base.len())
+            .map(|out| {
+                nudged
+                    .iter()
+                    .map(|outputs| (outputs[out] - base[out]) / epsilon)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Check there is a value for each of a model's `count` parameters
+fn check_parameter_count(count: usize, parameters: &[f64]) -> Result<(), Box<dyn Error>> {
+    match parameters.len() {
+        n if n == count => Ok(()),
+        n => Err(format!("the model has {count} parameters, but {n} values were given").into()),
+    }
+}
+
+/// Regressor of fixed size, as a [`Regressor`] of runtime size
+#[cfg(feature = "nightly")]
+#[derive(Clone, Debug)]
+pub struct Fixed<R>(pub R);
+
+#[cfg(feature = "nightly")]
+impl<R: Display> Display for Fixed<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "nightly")]
+impl<R> Regressor for Fixed<R>
+where
+    R: GradientDescent + Display,
+    [(); R::IN_DIMENSION]:,
+    [(); R::PARAM_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    fn input_count(&self) -> usize {
+        R::IN_DIMENSION
+    }
+
+    fn param_count(&self) -> usize {
+        R::PARAM_DIMENSION
+    }
+
+    fn output_count(&self) -> usize {
+        R::OUT_DIMENSION
+    }
+
+    fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64]) -> Vec<f64> {
+        let input = array::from_fn(|i| input[i]);
+        self.0.predict(nudge, &input).to_vec()
+    }
+
+    fn parameters(&self) -> Vec<f64> {
+        self.0.parameters().to_vec()
+    }
+
+    fn set_parameters(&mut self, parameters: &[f64]) -> Result<(), Box<dyn Error>> {
+        check_parameter_count(R::PARAM_DIMENSION, parameters)?;
+        self.0.set_parameters(array::from_fn(|i| parameters[i]));
+        Ok(())
+    }
+
+    fn descend(&mut self, adjustments: &[f64]) {
+        self.0.descend(array::from_fn(|i| adjustments[i]));
+    }
+
+    fn parameter_names(&self) -> Vec<String> {
+        self.0.parameter_names().to_vec()
+    }
+
+    fn jacobian(&self, input: &[f64], epsilon: f64) -> Vec<Vec<f64>> {
+        let input = array::from_fn(|i| input[i]);
+        self.0
+            .jacobian(&input, epsilon)
+            .iter()
+            .map(|row| row.to_vec())
+            .collect()
+    }
+}
+
+/// Polynomial of any degree, constant term first
+#[derive(Clone, Debug)]
+pub struct Polynomial {
+    pub terms: Vec<f64>,
+}
+
+impl Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_polynomial(f, &self.terms)
+    }
+}
+
+impl Regressor for Polynomial {
+    fn input_count(&self) -> usize {
+        1
+    }
+
+    fn param_count(&self) -> usize {
+        self.terms.len()
+    }
+
+    fn output_count(&self) -> usize {
+        1
+    }
+
+    fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64]) -> Vec<f64> {
+        let mut terms = self.terms.clone();
+        apply_nudge(&mut terms, nudge);
+        vec![evaluate_polynomial(terms, input[0])]
+    }
+
+    fn parameters(&self) -> Vec<f64> {
+        self.terms.clone()
+    }
+
+    fn set_parameters(&mut self, parameters: &[f64]) -> Result<(), Box<dyn Error>> {
+        check_parameter_count(self.terms.len(), parameters)?;
+        self.terms.copy_from_slice(parameters);
+        Ok(())
+    }
+
+    fn descend(&mut self, adjustments: &[f64]) {
+        for (term, adjustment) in self.terms.iter_mut().zip(adjustments) {
+            *term += adjustment;
+        }
+    }
+
+    fn parameter_names(&self) -> Vec<String> {
+        (0..self.terms.len()).map(|i| format!("a{i}")).collect()
+    }
+
+    fn jacobian(&self, input: &[f64], _epsilon: f64) -> Vec<Vec<f64>> {
+        vec![polynomial_derivatives(self.terms.len(), input[0]).collect()]
+    }
+}
+
+/// Expressions of one input sharing any number of parameters, an output for each
+#[derive(Clone, Debug)]
+pub struct Formula {
+    pub parameters: Vec<f64>,
+    pub expressions: Vec<Expression>,
+}
+
+impl Formula {
+    /// Parse expressions separated by `;`, starting every parameter at 1
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let expressions = Expression::parse_system(source)?;
+        Ok(Self {
+            parameters: vec![1.0; expressions[0].parameters().len()],
+            expressions,
+        })
+    }
+}
+
+impl Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_formula(f, &self.expressions, &self.parameters)
+    }
+}
+
+impl Regressor for Formula {
+    fn input_count(&self) -> usize {
+        1
+    }
+
+    fn param_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    fn output_count(&self) -> usize {
+        self.expressions.len()
+    }
+
+    fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64]) -> Vec<f64> {
+        let mut parameters = self.parameters.clone();
+        apply_nudge(&mut parameters, nudge);
+        self.expressions
+            .iter()
+            .map(|expression| expression.evaluate(input[0], &parameters))
+            .collect()
+    }
+
+    fn parameters(&self) -> Vec<f64> {
+        self.parameters.clone()
+    }
+
+    fn set_parameters(&mut self, parameters: &[f64]) -> Result<(), Box<dyn Error>> {
+        check_parameter_count(self.parameters.len(), parameters)?;
+        self.parameters.copy_from_slice(parameters);
+        Ok(())
+    }
+
+    fn descend(&mut self, adjustments: &[f64]) {
+        for (parameter, adjustment) in self.parameters.iter_mut().zip(adjustments) {
+            *parameter += adjustment;
+        }
+    }
+
+    fn parameter_names(&self) -> Vec<String> {
+        self.expressions[0].parameters().to_vec()
+    }
+
+    /// Exact derivatives, differentiating with respect to one parameter at a time since their
+    /// number isn't known at compile time
+    fn jacobian(&self, input: &[f64], _epsilon: f64) -> Vec<Vec<f64>> {
+        let columns = (0..self.parameters.len())
+            .map(|variable| {
+                let parameters = self
+                    .parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        if i == variable {
+                            Dual::<1>::variable(*value, 0)
+                        } else {
+                            Dual::constant(*value)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                self.expressions
+                    .iter()
+                    .map(|expression| expression.evaluate(input[0], &parameters).derivative(0))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        (0..self.expressions.len())
+            .map(|out| columns.iter().map(|column| column[out]).collect())
+            .collect()
+    }
+}
+
+/// One observation for a model of runtime size: its inputs, the outputs observed for them, and
+/// the weight of each output
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub weight: Vec<f64>,
+}
+
+impl<const N: usize, const M: usize> From<Record<N, M>> for Sample {
+    fn from(record: Record<N, M>) -> Self {
+        Self {
+            x: record.x.to_vec(),
+            y: record.y.to_vec(),
+            weight: record.weight.to_vec(),
+        }
+    }
+}
+
+/// How to fit a [`Regressor`]. Squared error is minimized by Levenberg–Marquardt, and other
+/// losses by plain gradient descent
+#[derive(Clone, Debug)]
+pub struct FitOptions {
+    pub loss: Loss,
+    /// Learning rate of the gradient descent
+    pub learning_rate: f64,
+    /// Initial damping factor of Levenberg–Marquardt
+    pub damping: f64,
+    /// Weight of the error in each output; if empty, every output counts the same
+    pub output_weights: Vec<f64>,
+    /// Step of the finite differences, for models that don't know their derivatives exactly
+    pub epsilon: f64,
+    /// Finish when the magnitude of the gradient falls below this value
+    pub finish_threshold: f64,
+    /// Stop after this many iterations even if the fit hasn't converged
+    pub max_iterations: Option<usize>,
+}
+
+impl Default for FitOptions {
+    fn default() -> Self {
+        Self {
+            loss: Loss::Squared,
+            learning_rate: 1e-3,
+            damping: 1e-3,
+            output_weights: Vec::new(),
+            epsilon: 1e-8,
+            finish_threshold: 1e-8,
+            max_iterations: None,
+        }
+    }
+}
+
+/// How a fit by [`fit`] went; the fitted parameters are left in the regressor
+#[derive(Clone, Debug)]
+pub struct FitResult {
+    /// Number of iterations taken
+    pub iterations: usize,
+    /// Mean loss before the last iteration's step
+    pub error: f64,
+    /// Whether the fit finished by converging rather than by running out of iterations
+    pub converged: bool,
+    /// Weight of the error in each output, as used in the fit
+    pub output_weights: Vec<f64>,
+    /// Least squares covariance of the fitted parameters, if they are identifiable from the data
+    pub covariance: Option<DynamicCovariance>,
+}
+
+/// Fit the parameters of `regressor` to `data`, starting from its current ones
+pub fn fit(
+    regressor: &mut dyn Regressor,
+    data: &[Sample],
+    options: &FitOptions,
+) -> Result<FitResult, Box<dyn Error>> {
+    let (inputs, outputs) = (regressor.input_count(), regressor.output_count());
+    for (i, sample) in data.iter().enumerate() {
+        if sample.x.len() != inputs {
+            return Err(format!(
+                "sample {i} has {} inputs, but the model takes {inputs}",
+                sample.x.len()
+            )
+            .into());
+        }
+        if sample.y.len() != outputs || sample.weight.len() != outputs {
+            return Err(format!(
+                "sample {i} has {} outputs and {} weights, but the model has {outputs} outputs",
+                sample.y.len(),
+                sample.weight.len()
+            )
+            .into());
+        }
+    }
+    let output_weights = output_weights(&options.output_weights, outputs)?;
+    let total_weight = weight_sum(
+        data.iter().map(|sample| sample.weight.as_slice()),
+        &output_weights,
+    );
+    if total_weight == 0.0 {
+        return Err("no data to fit".into());
+    }
+
+    let mut damping = options.damping;
+    let mut iteration = 0;
+    loop {
+        let (error, gradients, progressed) = match options.loss {
+            Loss::Squared => {
+                let (residuals, jacobian) =
+                    residuals_and_jacobian(regressor, data, &output_weights, options.epsilon);
+                let parameters = regressor.param_count();
+                let (error, gradient, progressed) = LevenbergMarquardt::damped_step(
+                    &mut damping,
+                    regressor,
+                    parameters,
+                    &residuals,
+                    &jacobian,
+                    |regressor, step| regressor.descend(step),
+                    |regressor| squared_error(regressor, data, &output_weights),
+                );
+                // report the mean error and its gradient like the descent does
+                let gradient = gradient.iter().map(|x| 2.0 * x / total_weight).collect();
+                (error / total_weight, gradient, progressed)
+            }
+            loss => {
+                let mut error = 0.0;
+                let mut gradients = vec![0.0; regressor.param_count()];
+                for sample in data {
+                    let prediction = regressor.predict(None, &sample.x);
+                    let jacobian = regressor.jacobian(&sample.x, options.epsilon);
+                    error += weighted_loss(
+                        loss,
+                        &sample.y,
+                        &sample.weight,
+                        &output_weights,
+                        &prediction,
+                        &jacobian,
+                        &mut gradients,
+                    );
+                }
+                for gradient in &mut gradients {
+                    *gradient /= total_weight;
+                }
+                let adjustments = gradients
+                    .iter()
+                    .map(|gradient| gradient * options.learning_rate)
+                    .collect::<Vec<_>>();
+                regressor.descend(&adjustments);
+                (error / total_weight, gradients, true)
+            }
+        };
+        let magnitude = gradients.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let converged = magnitude <= options.finish_threshold || !progressed;
+        if converged
+            || options
+                .max_iterations
+                .is_some_and(|max| iteration + 1 >= max)
+        {
+            let (residuals, jacobian) =
+                residuals_and_jacobian(regressor, data, &output_weights, options.epsilon);
+            return Ok(FitResult {
+                iterations: iteration + 1,
+                error,
+                converged,
+                covariance: DynamicCovariance::new(&jacobian, &residuals, regressor.param_count()),
+                output_weights,
+            });
+        }
+        iteration += 1;
+    }
+}
+
+/// Residual of each output of the regressor at each sample, sample by sample, and the jacobian
+/// of that output there, both scaled by the square root of the output's weight
+fn residuals_and_jacobian(
+    regressor: &dyn Regressor,
+    data: &[Sample],
+    output_weights: &[f64],
+    epsilon: f64,
+) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let mut residuals = Vec::new();
+    let mut rows = Vec::new();
+    for sample in data {
+        let prediction = regressor.predict(None, &sample.x);
+        let mut jacobian = regressor.jacobian(&sample.x, epsilon);
+        weigh_residuals(
+            &sample.y,
+            &sample.weight,
+            output_weights,
+            &prediction,
+            &mut jacobian,
+            &mut residuals,
+        );
+        rows.extend(jacobian);
+    }
+    (residuals, rows)
+}
+
+fn squared_error(regressor: &dyn Regressor, data: &[Sample], output_weights: &[f64]) -> f64 {
+    data.iter()
+        .map(|sample| {
+            let prediction = regressor.predict(None, &sample.x);
+            weighted_squared_error(&sample.y, &sample.weight, output_weights, &prediction)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit, FitOptions, Formula, Polynomial, Regressor, Sample};
+    use crate::losses::Loss;
+
+    #[test]
+    fn fits_boxed_models() {
+        // a degree and a formula chosen at runtime, with more parameters than the fixed size
+        // regressors are instantiated for
+        let terms = [1.0, -0.5, 0.25, 0.1, -0.02, 3e-3, -2e-4, 1e-5, -3e-7, 4e-9];
+        let data = (0..40)
+            .map(|i| {
+                let x = i as f64 / 10.0 - 2.0;
+                let y = terms.iter().rev().fold(0.0, |sum, term| sum * x + term);
+                Sample {
+                    x: vec![x],
+                    y: vec![y, 2.0 * x + 1.0],
+                    weight: vec![1.0, 1.0],
+                }
+            })
+            .collect::<Vec<_>>();
+        let polynomial = data
+            .iter()
+            .map(|sample| Sample {
+                y: vec![sample.y[0]],
+                weight: vec![1.0],
+                ..sample.clone()
+            })
+            .collect::<Vec<_>>();
+        let source = "a + b*x + c*x^2 + d*x^3 + e*x^4 + f*x^5 + g*x^6 + h*x^7 + i*x^8 + j*x^9; \
+            k * x + l";
+        let mut models: Vec<(Box<dyn Regressor>, &[Sample])> = vec![
+            (
+                Box::new(Polynomial {
+                    terms: vec![0.0; terms.len()],
+                }),
+                &polynomial,
+            ),
+            (Box::new(Formula::parse(source).unwrap()), &data),
+        ];
+        for (model, data) in &mut models {
+            let result = fit(model.as_mut(), data, &FitOptions::default()).unwrap();
+            assert!(result.converged);
+            assert!(result.covariance.is_some());
+            let parameters = model.parameters();
+            for (parameter, expected) in parameters.iter().zip(terms.iter().chain(&[2.0, 1.0])) {
+                assert!((parameter - expected).abs() < 1e-6 * expected.abs().max(1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn descends_other_losses() {
+        let data = (0..10)
+            .map(|i| Sample {
+                x: vec![i as f64],
+                y: vec![3.0 - 0.5 * i as f64],
+                weight: vec![1.0],
+            })
+            .collect::<Vec<_>>();
+        let mut model = Polynomial {
+            terms: vec![0.0, 0.0],
+        };
+        let options = FitOptions {
+            loss: Loss::Huber { delta: 1.0 },
+            learning_rate: 0.02,
+            max_iterations: Some(20_000),
+            ..FitOptions::default()
+        };
+        fit(&mut model, &data, &options).unwrap();
+        assert!((model.terms[0] - 3.0).abs() < 1e-3);
+        assert!((model.terms[1] + 0.5).abs() < 1e-3);
+        let wrong = Sample {
+            x: vec![0.0, 1.0],
+            ..data[0].clone()
+        };
+        assert!(fit(&mut model, &[wrong], &FitOptions::default()).is_err());
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn adapts_fixed_size_regressors() {
+        use super::Fixed;
+        use crate::regressors::Exponential;
+
+        let data = (0..20)
+            .map(|i| Sample {
+                x: vec![i as f64 / 4.0],
+                y: vec![2.5 * 1.3f64.powf(i as f64 / 4.0)],
+                weight: vec![1.0],
+            })
+            .collect::<Vec<_>>();
+        let mut model: Box<dyn Regressor> = Box::new(Fixed(Exponential {
+            base: 1.0,
+            growth: 1.0,
+        }));
+        assert_eq!(model.param_count(), 2);
+        fit(model.as_mut(), &data, &FitOptions::default()).unwrap();
+        let parameters = model.parameters();
+        assert!((parameters[0] - 2.5).abs() < 1e-9);
+        assert!((parameters[1] - 1.3).abs() < 1e-9);
+        assert_eq!(model.parameter_names(), ["base", "growth"]);
+    }
+
+    #[test]
+    fn sets_and_names_parameters() {
+        let mut formula = Formula::parse("a * x + b; b - x").unwrap();
+        assert_eq!(formula.parameter_names(), ["a", "b"]);
+        formula.set_parameters(&[2.0, 3.0]).unwrap();
+        assert_eq!(formula.parameters(), [2.0, 3.0]);
+        assert_eq!(formula.predict(None, &[1.0]), [5.0, 2.0]);
+
+        let mut polynomial = Polynomial {
+            terms: vec![0.0; 3],
+        };
+        polynomial.set_parameters(&[1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(polynomial.predict(None, &[2.0]), [17.0]);
+        assert_eq!(polynomial.parameter_names(), ["a0", "a1", "a2"]);
+        // the values are left alone if there aren't as many as parameters
+        assert!(polynomial.set_parameters(&[1.0, 2.0]).is_err());
+        assert!(formula.set_parameters(&[1.0, 2.0, 3.0]).is_err());
+        assert_eq!(polynomial.parameters(), [1.0, 2.0, 3.0]);
+    }
+}
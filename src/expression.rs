@@ -0,0 +1,531 @@
+use std::{error::Error, fmt::Display, iter::Peekable, str::CharIndices};
+
+use crate::dual::Scalar;
+
+/// Maximum depth of the evaluation stack a compiled expression may need
+const STACK_SIZE: usize = 64;
+
+/// Functions that can be called from an expression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Builtin {
+    Exp,
+    Ln,
+    Sin,
+    Cos,
+    Tan,
+    Tanh,
+    Abs,
+    Sqrt,
+    Pow,
+}
+
+impl Builtin {
+    const ALL: [Builtin; 9] = [
+        Builtin::Exp,
+        Builtin::Ln,
+        Builtin::Sin,
+        Builtin::Cos,
+        Builtin::Tan,
+        Builtin::Tanh,
+        Builtin::Abs,
+        Builtin::Sqrt,
+        Builtin::Pow,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Builtin::Exp => "exp",
+            Builtin::Ln => "ln",
+            Builtin::Sin => "sin",
+            Builtin::Cos => "cos",
+            Builtin::Tan => "tan",
+            Builtin::Tanh => "tanh",
+            Builtin::Abs => "abs",
+            Builtin::Sqrt => "sqrt",
+            Builtin::Pow => "pow",
+        }
+    }
+
+    fn arguments(self) -> usize {
+        match self {
+            Builtin::Pow => 2,
+            _ => 1,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Builtin> {
+        Builtin::ALL
+            .into_iter()
+            .find(|builtin| builtin.name() == name)
+    }
+}
+
+/// A single instruction of a compiled expression, evaluated on a stack
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Constant(f64),
+    Input,
+    Parameter(usize),
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Call(Builtin),
+}
+
+/// A formula in `x` and any number of named parameters, such as `a * exp(-b * x) + c`,
+/// compiled to a sequence of stack operations so that it can be evaluated quickly
+#[derive(Clone, Debug)]
+pub struct Expression {
+    source: String,
+    parameters: Vec<String>,
+    ops: Vec<Op>,
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at position {}: {}", self.position, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Symbol(char),
+    End,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    token: Token,
+    position: usize,
+    parameters: Vec<String>,
+    ops: Vec<Op>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            source,
+            chars: source.char_indices().peekable(),
+            token: Token::End,
+            position: 0,
+            parameters: Vec::new(),
+            ops: Vec::new(),
+        };
+        parser.advance()?;
+        Ok(parser)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            position: self.position,
+            message: message.into(),
+        }
+    }
+
+    fn advance(&mut self) -> Result<(), ParseError> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some(&(start, c)) = self.chars.peek() else {
+            self.position = self.source.len();
+            self.token = Token::End;
+            return Ok(());
+        };
+        self.position = start;
+        self.token = if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            let mut previous = c;
+            while let Some((i, c)) = self.chars.next_if(|&(_, c)| {
+                c.is_ascii_digit()
+                    || c == '.'
+                    || c == 'e'
+                    || c == 'E'
+                    || ((c == '-' || c == '+') && (previous == 'e' || previous == 'E'))
+            }) {
+                end = i + c.len_utf8();
+                previous = c;
+            }
+            let text = &self.source[start..end];
+            Token::Number(
+                text.parse()
+                    .map_err(|_| self.error(format!("invalid number `{text}`")))?,
+            )
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some((i, c)) = self
+                .chars
+                .next_if(|(_, c)| c.is_alphanumeric() || *c == '_')
+            {
+                end = i + c.len_utf8();
+            }
+            Token::Identifier(self.source[start..end].to_string())
+        } else if "+-*/^(),".contains(c) {
+            self.chars.next();
+            Token::Symbol(c)
+        } else {
+            return Err(self.error(format!("unexpected character `{c}`")));
+        };
+        Ok(())
+    }
+
+    fn eat(&mut self, symbol: char) -> Result<bool, ParseError> {
+        if self.token == Token::Symbol(symbol) {
+            self.advance()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ParseError> {
+        if self.eat(symbol)? {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{symbol}`")))
+        }
+    }
+
+    /// expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<(), ParseError> {
+        self.term()?;
+        loop {
+            if self.eat('+')? {
+                self.term()?;
+                self.ops.push(Op::Add);
+            } else if self.eat('-')? {
+                self.term()?;
+                self.ops.push(Op::Sub);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<(), ParseError> {
+        self.unary()?;
+        loop {
+            if self.eat('*')? {
+                self.unary()?;
+                self.ops.push(Op::Mul);
+            } else if self.eat('/')? {
+                self.unary()?;
+                self.ops.push(Op::Div);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// unary := '-' unary | power
+    fn unary(&mut self) -> Result<(), ParseError> {
+        if self.eat('-')? {
+            self.unary()?;
+            self.ops.push(Op::Neg);
+            Ok(())
+        } else {
+            self.power()
+        }
+    }
+
+    /// power := atom ('^' unary)?
+    fn power(&mut self) -> Result<(), ParseError> {
+        self.atom()?;
+        if self.eat('^')? {
+            self.unary()?;
+            self.ops.push(Op::Pow);
+        }
+        Ok(())
+    }
+
+    /// atom := number | identifier | identifier '(' arguments ')' | '(' expression ')'
+    fn atom(&mut self) -> Result<(), ParseError> {
+        match self.token.clone() {
+            Token::Number(value) => {
+                self.advance()?;
+                self.ops.push(Op::Constant(value));
+            }
+            Token::Symbol('(') => {
+                self.advance()?;
+                self.expression()?;
+                self.expect(')')?;
+            }
+            Token::Identifier(name) => {
+                let position = self.position;
+                self.advance()?;
+                if self.eat('(')? {
+                    let builtin = Builtin::from_name(&name).ok_or_else(|| ParseError {
+                        position,
+                        message: format!("unknown function `{name}`"),
+                    })?;
+                    for i in 0..builtin.arguments() {
+                        if i > 0 {
+                            self.expect(',')?;
+                        }
+                        self.expression()?;
+                    }
+                    self.expect(')')?;
+                    self.ops.push(Op::Call(builtin));
+                } else if name == "x" {
+                    self.ops.push(Op::Input);
+                } else if name == "pi" {
+                    self.ops.push(Op::Constant(std::f64::consts::PI));
+                } else if Builtin::from_name(&name).is_some() {
+                    return Err(ParseError {
+                        position,
+                        message: format!("function `{name}` must be called"),
+                    });
+                } else {
+                    let index = match self.parameters.iter().position(|p| *p == name) {
+                        Some(index) => index,
+                        None => {
+                            self.parameters.push(name);
+                            self.parameters.len() - 1
+                        }
+                    };
+                    self.ops.push(Op::Parameter(index));
+                }
+            }
+            Token::Symbol(c) => return Err(self.error(format!("unexpected `{c}`"))),
+            Token::End => return Err(self.error("unexpected end of expression")),
+        }
+        Ok(())
+    }
+}
+
+impl Expression {
+    /// Parse and compile an expression; identifiers other than `x`, `pi` and the builtin
+    /// functions become parameters, numbered in order of first appearance
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        Self::parse_with(source, Vec::new())
+    }
+
+    /// Parse expressions separated by `;` that share their parameters, such as the coordinates
+    /// of a trajectory. Parameters are numbered in order of first appearance across all of them,
+    /// and each expression takes the values of every one
+    pub fn parse_system(source: &str) -> Result<Vec<Self>, ParseError> {
+        let mut expressions = Vec::new();
+        let mut parameters = Vec::new();
+        let mut offset = 0;
+        for piece in source.split(';') {
+            let expression = Self::parse_with(piece, parameters).map_err(|mut err| {
+                err.position += offset;
+                err
+            })?;
+            parameters = expression.parameters.clone();
+            offset += piece.len() + 1;
+            expressions.push(expression);
+        }
+        for expression in &mut expressions {
+            expression.parameters.clone_from(&parameters);
+        }
+        Ok(expressions)
+    }
+
+    /// Parse an expression, numbering its parameters after the already known `parameters`
+    fn parse_with(source: &str, parameters: Vec<String>) -> Result<Self, ParseError> {
+        let mut parser = Parser::new(source)?;
+        parser.parameters = parameters;
+        parser.expression()?;
+        if parser.token != Token::End {
+            return Err(parser.error("expected an operator"));
+        }
+        let mut depth: usize = 0;
+        for op in &parser.ops {
+            depth = match op {
+                Op::Constant(_) | Op::Input | Op::Parameter(_) => depth + 1,
+                Op::Neg => depth,
+                Op::Call(builtin) => depth + 1 - builtin.arguments(),
+                Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Pow => depth - 1,
+            };
+            if depth > STACK_SIZE {
+                return Err(parser.error("expression is nested too deeply"));
+            }
+        }
+        Ok(Expression {
+            source: source.trim().to_string(),
+            parameters: parser.parameters,
+            ops: parser.ops,
+        })
+    }
+
+    /// Names of the parameters, in the order `evaluate` expects their values
+    pub fn parameters(&self) -> &[String] {
+        &self.parameters
+    }
+
+    pub fn evaluate<S: Scalar>(&self, x: f64, parameters: &[S]) -> S {
+        let mut stack = [S::constant(0.0); STACK_SIZE];
+        let mut top = 0;
+        for op in &self.ops {
+            match *op {
+                Op::Constant(value) => {
+                    stack[top] = S::constant(value);
+                    top += 1;
+                }
+                Op::Input => {
+                    stack[top] = S::constant(x);
+                    top += 1;
+                }
+                Op::Parameter(i) => {
+                    stack[top] = parameters[i];
+                    top += 1;
+                }
+                Op::Neg => stack[top - 1] = -stack[top - 1],
+                Op::Call(Builtin::Pow) | Op::Pow => {
+                    top -= 1;
+                    stack[top - 1] = stack[top - 1].powf(stack[top]);
+                }
+                Op::Call(builtin) => {
+                    let a = stack[top - 1];
+                    stack[top - 1] = match builtin {
+                        Builtin::Exp => a.exp(),
+                        Builtin::Ln => a.ln(),
+                        Builtin::Sin => a.sin(),
+                        Builtin::Cos => a.cos(),
+                        Builtin::Tan => a.tan(),
+                        Builtin::Tanh => a.tanh(),
+                        Builtin::Abs => a.abs(),
+                        Builtin::Sqrt => a.sqrt(),
+                        Builtin::Pow => unreachable!(),
+                    };
+                }
+                Op::Add | Op::Sub | Op::Mul | Op::Div => {
+                    top -= 1;
+                    let (a, b) = (stack[top - 1], stack[top]);
+                    stack[top - 1] = match op {
+                        Op::Add => a + b,
+                        Op::Sub => a - b,
+                        Op::Mul => a * b,
+                        _ => a / b,
+                    };
+                }
+            }
+        }
+        stack[0]
+    }
+
+    /// Render the expression with each parameter replaced by its value
+    pub fn substitute(&self, parameters: &[f64]) -> String {
+        // each entry is the rendered subexpression and the precedence of its outermost operator
+        let mut stack: Vec<(String, u8)> = Vec::new();
+        let wrap = |(text, precedence): (String, u8), minimum: u8| {
+            if precedence < minimum {
+                format!("({text})")
+            } else {
+                text
+            }
+        };
+        for op in &self.ops {
+            let entry = match *op {
+                Op::Constant(value) => (value.to_string(), if value < 0.0 { 2 } else { 4 }),
+                Op::Input => ("x".to_string(), 4),
+                Op::Parameter(i) => {
+                    let value = parameters[i];
+                    (value.to_string(), if value < 0.0 { 2 } else { 4 })
+                }
+                Op::Neg => {
+                    let a = stack.pop().unwrap();
+                    (format!("-{}", wrap(a, 3)), 2)
+                }
+                Op::Call(builtin) => {
+                    let arguments = stack
+                        .split_off(stack.len() - builtin.arguments())
+                        .into_iter()
+                        .map(|(text, _)| text)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    (format!("{}({arguments})", builtin.name()), 4)
+                }
+                Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Pow => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    let (symbol, precedence) = match op {
+                        Op::Add => ("+", 0),
+                        Op::Sub => ("-", 0),
+                        Op::Mul => ("*", 1),
+                        Op::Div => ("/", 1),
+                        _ => ("^", 3),
+                    };
+                    let (left, right) = match op {
+                        Op::Pow => (wrap(a, 4), wrap(b, 2)),
+                        _ => (wrap(a, precedence), wrap(b, precedence + 1)),
+                    };
+                    (format!("{left} {symbol} {right}"), precedence)
+                }
+            };
+            stack.push(entry);
+        }
+        stack.pop().map(|(text, _)| text).unwrap_or_default()
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Expression;
+
+    #[test]
+    fn evaluate() {
+        let expression = Expression::parse("a * exp(-b * x) + c").unwrap();
+        assert_eq!(expression.parameters(), ["a", "b", "c"]);
+        let value = expression.evaluate(2.0, &[3.0, 0.5, 1.0]);
+        assert!((value - (3.0 * (-1.0f64).exp() + 1.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn precedence() {
+        let expression = Expression::parse("-x^2 + 2^-1 * pow(x, 3) / 4 - sqrt(abs(x))").unwrap();
+        let x: f64 = -2.0;
+        let expected = -x.powi(2) + 0.5 * x.powi(3) / 4.0 - x.abs().sqrt();
+        assert!((expression.evaluate::<f64>(x, &[]) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn substitute() {
+        let expression = Expression::parse("a * (x - b) ^ 2 - -c").unwrap();
+        assert_eq!(
+            expression.substitute(&[2.0, -1.0, 3.0]),
+            "2 * (x - -1) ^ 2 - -3"
+        );
+    }
+
+    #[test]
+    fn system() {
+        let expressions = Expression::parse_system("a + v * x; b - g / 2 * x ^ 2 + a").unwrap();
+        assert_eq!(expressions.len(), 2);
+        assert_eq!(expressions[0].parameters(), ["a", "v", "b", "g"]);
+        assert_eq!(expressions[1].parameters(), expressions[0].parameters());
+        let parameters = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(expressions[0].evaluate(2.0, &parameters), 5.0);
+        assert_eq!(expressions[1].evaluate(2.0, &parameters), -4.0);
+        assert_eq!(Expression::parse_system("x; a *").unwrap_err().position, 6);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Expression::parse("a * ").unwrap_err().position, 4);
+        assert_eq!(Expression::parse("foo(x)").unwrap_err().position, 0);
+        assert_eq!(Expression::parse("x $ 2").unwrap_err().position, 2);
+        assert!(Expression::parse("pow(x)").is_err());
+        assert!(Expression::parse("exp").is_err());
+        assert!(Expression::parse("(x").is_err());
+    }
+}
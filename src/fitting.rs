@@ -0,0 +1,349 @@
+use std::error::Error;
+
+use clap::ValueEnum;
+
+use crate::{
+    error_gradients,
+    history::{Entry, History},
+    losses::Loss,
+    optimizers::{Optimizer, Sgd},
+    output_weights, residuals_and_jacobian,
+    solvers::{linear_least_squares, LevenbergMarquardt},
+    statistics::Covariance,
+    total_weight, GradientDescent, Magnitude, Record,
+};
+
+/// Method used to fit the model's parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Solver {
+    /// Direct least squares for models linear in their parameters, gradient descent otherwise
+    Auto,
+    /// Exact least squares in a single solve; only for models linear in their parameters
+    Direct,
+    /// Iterative gradient descent using the optimizer
+    Descent,
+    /// Damped Gauss-Newton least squares, finishing once no step reduces the error
+    LevenbergMarquardt,
+}
+
+/// How to fit a regressor with `P` parameters
+pub struct FitOptions<const P: usize> {
+    pub solver: Solver,
+    pub loss: Loss,
+    /// Update rule of the descent solver
+    pub optimizer: Box<dyn Optimizer<P>>,
+    /// Initial damping factor of the Levenberg-Marquardt solver
+    pub damping: f64,
+    /// Weight of the error in each output; if empty, every output counts the same
+    pub output_weights: Vec<f64>,
+    /// Estimate gradients with finite differences even for models whose derivatives are known
+    /// exactly
+    pub finite_differences: bool,
+    /// Step of the finite differences
+    pub epsilon: f64,
+    /// Finish when the magnitude of the gradient falls below this value
+    pub finish_threshold: f64,
+    /// Stop after this many iterations even if the fit hasn't converged
+    pub max_iterations: Option<usize>,
+}
+
+impl<const P: usize> Default for FitOptions<P> {
+    fn default() -> Self {
+        Self {
+            solver: Solver::Auto,
+            loss: Loss::Squared,
+            optimizer: Box::new(Sgd {
+                learning_rate: 1e-10,
+            }),
+            damping: 1e-3,
+            output_weights: Vec::new(),
+            finite_differences: false,
+            epsilon: 1e-8,
+            finish_threshold: 1e-8,
+            max_iterations: None,
+        }
+    }
+}
+
+impl<const P: usize> FitOptions<P> {
+    /// Weight of the error in each of the model's `outputs`, checking there is one for each
+    pub fn output_weights(&self, outputs: usize) -> Result<Vec<f64>, Box<dyn Error>> {
+        output_weights(&self.output_weights, outputs)
+    }
+}
+
+/// State of a fit after one of its iterations
+pub struct Iteration<'a, R> {
+    pub iteration: usize,
+    /// Mean loss of the parameters from before the iteration's step, or of the exact solution of
+    /// the direct solver
+    pub error: f64,
+    /// Magnitude of the gradient of the loss at those parameters
+    pub magnitude: f64,
+    pub regressor: &'a R,
+    /// Whether this is the last iteration of the fit
+    pub finished: bool,
+}
+
+/// Regressor fitted by [`fit`], and how the fit went
+pub struct FitResult<R: GradientDescent>
+where
+    [(); R::PARAM_DIMENSION]:,
+{
+    pub regressor: R,
+    /// Number of iterations taken
+    pub iterations: usize,
+    /// Mean loss before the last iteration's step, or of the exact solution of the direct solver
+    pub error: f64,
+    /// Whether the fit finished by converging rather than by running out of iterations
+    pub converged: bool,
+    pub history: History<{ R::PARAM_DIMENSION }>,
+    /// Weight of the error in each output, as used in the fit
+    pub output_weights: Vec<f64>,
+    /// Least squares covariance of the fitted parameters, if they are identifiable from the data
+    pub covariance: Option<Covariance<{ R::PARAM_DIMENSION }>>,
+}
+
+/// Fit the parameters of `regressor` to `data`, starting from its current ones
+pub fn fit<R>(
+    regressor: R,
+    data: &[Record<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>],
+    options: FitOptions<{ R::PARAM_DIMENSION }>,
+) -> Result<FitResult<R>, Box<dyn Error>>
+where
+    R: GradientDescent,
+    [(); R::IN_DIMENSION]:,
+    [(); R::PARAM_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    fit_with(regressor, data, options, |_| Ok(()))
+}
+
+/// Like [`fit`], calling `observe` after every iteration, such as to report progress. An error
+/// from `observe` stops the fit and is returned
+pub fn fit_with<R>(
+    mut regressor: R,
+    data: &[Record<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>],
+    mut options: FitOptions<{ R::PARAM_DIMENSION }>,
+    mut observe: impl FnMut(&Iteration<R>) -> Result<(), Box<dyn Error>>,
+) -> Result<FitResult<R>, Box<dyn Error>>
+where
+    R: GradientDescent,
+    [(); R::IN_DIMENSION]:,
+    [(); R::PARAM_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    let least_squares = matches!(options.loss, Loss::Squared);
+    let solver = match options.solver {
+        Solver::Auto if R::LINEAR && least_squares => Solver::Direct,
+        Solver::Auto => Solver::Descent,
+        Solver::Direct | Solver::LevenbergMarquardt if !least_squares => {
+            return Err(format!(
+                "the {:?} solver only minimizes squared error, not {:?}",
+                options.solver, options.loss
+            )
+            .into())
+        }
+        Solver::Direct if !R::LINEAR => {
+            return Err("the direct solver requires a model linear in its parameters".into())
+        }
+        solver => solver,
+    };
+    let output_weights = options.output_weights(R::OUT_DIMENSION)?;
+    if total_weight(data, &output_weights) == 0.0 {
+        return Err("no data to fit".into());
+    }
+    let mut history = History::<{ R::PARAM_DIMENSION }>::default();
+    let mut levenberg_marquardt = LevenbergMarquardt {
+        damping: options.damping,
+        output_weights: output_weights.clone(),
+        finite_differences: options.finite_differences,
+        epsilon: options.epsilon,
+    };
+    let mut iteration = 0;
+    let (error, converged) = loop {
+        // the error and gradients are of the parameters from before this iteration's step
+        let mut parameters = regressor.parameters();
+        let (error, gradients, progressed) = match solver {
+            Solver::Auto => unreachable!(),
+            Solver::Direct => {
+                if !linear_least_squares(
+                    &mut regressor,
+                    data,
+                    &output_weights,
+                    options.finite_differences,
+                    options.epsilon,
+                ) {
+                    return Err("the least squares problem has no unique solution".into());
+                }
+                // the solution is exact, so report its error and leave nothing left to do
+                parameters = regressor.parameters();
+                let (error, gradients) = error_gradients(
+                    &regressor,
+                    data,
+                    options.loss,
+                    &output_weights,
+                    options.finite_differences,
+                    options.epsilon,
+                );
+                (error, gradients, false)
+            }
+            Solver::Descent => {
+                let (error, gradients) = error_gradients(
+                    &regressor,
+                    data,
+                    options.loss,
+                    &output_weights,
+                    options.finite_differences,
+                    options.epsilon,
+                );
+                regressor.descend(options.optimizer.step(gradients));
+                (error, gradients, true)
+            }
+            Solver::LevenbergMarquardt => {
+                // the solver works with sums of squares; report them as means like the others
+                let (error, gradients, progressed) = levenberg_marquardt.step(&mut regressor, data);
+                let total_weight = total_weight(data, &output_weights);
+                (
+                    error / total_weight,
+                    gradients.map(|gradient| gradient / total_weight),
+                    progressed,
+                )
+            }
+        };
+        let magnitude = gradients.magnitude();
+        let converged = magnitude.abs() <= options.finish_threshold || !progressed;
+        let finished = converged
+            || options
+                .max_iterations
+                .is_some_and(|max| iteration + 1 >= max);
+        history.record(
+            Entry {
+                iteration,
+                error,
+                magnitude,
+                parameters,
+            },
+            finished,
+        );
+        observe(&Iteration {
+            iteration,
+            error,
+            magnitude,
+            regressor: &regressor,
+            finished,
+        })?;
+        if finished {
+            break (error, converged);
+        }
+        iteration += 1;
+    };
+
+    let (residuals, jacobian) = residuals_and_jacobian(
+        &regressor,
+        data,
+        &output_weights,
+        options.finite_differences,
+        options.epsilon,
+    );
+    Ok(FitResult {
+        covariance: Covariance::new(&jacobian, &residuals),
+        regressor,
+        iterations: iteration + 1,
+        error,
+        converged,
+        history,
+        output_weights,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit, FitOptions, Solver};
+    use crate::{
+        losses::Loss,
+        regressors::{Exponential, Linear},
+        Record,
+    };
+
+    #[test]
+    fn fits_linear() {
+        let data = (0..10)
+            .map(|i| Record {
+                x: [i as f64],
+                y: [2.0 * i as f64 + 1.0],
+                weight: [1.0],
+            })
+            .collect::<Vec<_>>();
+        let regressor = Linear {
+            slope: 0.0,
+            y_intercept: 0.0,
+        };
+        let result = fit(regressor, &data, FitOptions::default()).unwrap();
+        assert!(result.converged);
+        assert_eq!(result.iterations, 1);
+        assert!((result.regressor.slope - 2.0).abs() < 1e-9);
+        assert!((result.regressor.y_intercept - 1.0).abs() < 1e-9);
+        assert!(result.covariance.is_some());
+
+        let options = FitOptions {
+            solver: Solver::Direct,
+            loss: Loss::Absolute,
+            ..FitOptions::default()
+        };
+        assert!(fit(result.regressor, &data, options).is_err());
+    }
+
+    #[test]
+    fn reports_error_of_direct_solution() {
+        // noise of ±0.1 leaves a mean squared error of just under 0.01 at the optimum
+        let data = (0..20)
+            .map(|i| Record {
+                x: [i as f64],
+                y: [2.0 * i as f64 + 1.0 + if i % 2 == 0 { 0.1 } else { -0.1 }],
+                weight: [1.0],
+            })
+            .collect::<Vec<_>>();
+        let regressor = Linear {
+            slope: -5.0,
+            y_intercept: 40.0,
+        };
+        let options = FitOptions {
+            solver: Solver::Direct,
+            ..FitOptions::default()
+        };
+        let result = fit(regressor, &data, options).unwrap();
+        assert!(
+            result.error > 0.009 && result.error <= 0.01,
+            "{}",
+            result.error
+        );
+        let entry = &result.history.entries[0];
+        assert_eq!(entry.error, result.error);
+        assert_eq!(entry.parameters[0], result.regressor.slope);
+    }
+
+    #[test]
+    fn stops_after_max_iterations() {
+        let data = (0..10)
+            .map(|i| Record {
+                x: [i as f64],
+                y: [1.5f64.powi(i)],
+                weight: [1.0],
+            })
+            .collect::<Vec<_>>();
+        let regressor = Exponential {
+            base: 1.0,
+            growth: 1.0,
+        };
+        let options = FitOptions {
+            solver: Solver::Descent,
+            max_iterations: Some(5),
+            ..FitOptions::default()
+        };
+        let result = fit(regressor, &data, options).unwrap();
+        assert!(!result.converged);
+        assert_eq!(result.iterations, 5);
+        assert_eq!(result.history.entries.len(), 5);
+    }
+}
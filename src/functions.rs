@@ -0,0 +1,68 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::dual::Scalar;
+
+/// Named functions that can be plugged into the equation regressors from the command line
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Function {
+    /// e^(-x^2 / 2)
+    Gaussian,
+    /// 1 / (1 + e^-x)
+    Logistic,
+    /// tanh(x)
+    Tanh,
+    /// sin(x)
+    Sin,
+    /// e^x
+    Exp,
+    /// x^p0
+    Power,
+    /// x^-(x^p1 * p0), or 0 when x <= 0
+    PowerDecay,
+    /// sin(p0 * x + p1)
+    Sinusoid,
+}
+
+impl Function {
+    /// Number of shape parameters the function takes in addition to x
+    pub fn parameters(self) -> usize {
+        match self {
+            Function::Gaussian
+            | Function::Logistic
+            | Function::Tanh
+            | Function::Sin
+            | Function::Exp => 0,
+            Function::Power => 1,
+            Function::PowerDecay | Function::Sinusoid => 2,
+        }
+    }
+
+    /// Function pointer over the scalar `S` taking `P` shape parameters; `P` must equal
+    /// `self.parameters()`
+    pub fn parametric<S: Scalar, const P: usize>(self) -> fn(S, [S; P]) -> S {
+        assert_eq!(
+            P,
+            self.parameters(),
+            "{self:?} takes {} parameters",
+            self.parameters()
+        );
+        match self {
+            Function::Gaussian => |x, _| (-x * x / 2.0).exp(),
+            Function::Logistic => |x, _| S::constant(1.0) / ((-x).exp() + 1.0),
+            Function::Tanh => |x, _| x.tanh(),
+            Function::Sin => |x, _| x.sin(),
+            Function::Exp => |x, _| x.exp(),
+            Function::Power => |x, p| x.powf(p[0]),
+            Function::PowerDecay => |x, p| {
+                if x.value() <= 0.0 {
+                    S::constant(0.0)
+                } else {
+                    x.powf(-(x.powf(p[1]) * p[0]))
+                }
+            },
+            Function::Sinusoid => |x, p| (p[0] * x + p[1]).sin(),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::{error::Error, path::Path};
+
+use csv::Writer;
+
+/// State of the fit at one iteration
+#[derive(Clone, Debug)]
+pub struct Entry<const N: usize> {
+    pub iteration: usize,
+    pub error: f64,
+    pub magnitude: f64,
+    pub parameters: [f64; N],
+}
+
+/// Record of the fit over a run. Runs can take millions of iterations, so once the record gets
+/// long every other entry is dropped and only every `stride`th iteration is recorded from then on
+#[derive(Clone, Debug)]
+pub struct History<const N: usize> {
+    pub entries: Vec<Entry<N>>,
+    stride: usize,
+}
+
+impl<const N: usize> Default for History<N> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            stride: 1,
+        }
+    }
+}
+
+impl<const N: usize> History<N> {
+    const CAPACITY: usize = 10_000;
+
+    /// Record an iteration, unless it falls between strides. The last iteration of a run should
+    /// be recorded with `force` so the record ends at the final state
+    pub fn record(&mut self, entry: Entry<N>, force: bool) {
+        if self.entries.len() >= Self::CAPACITY {
+            self.stride *= 2;
+            let stride = self.stride;
+            self.entries
+                .retain(|entry| entry.iteration.is_multiple_of(stride));
+        }
+        if entry.iteration.is_multiple_of(self.stride) || force {
+            self.entries.push(entry);
+        }
+    }
+
+    /// Write every entry to a CSV file at `path`, one row per iteration, heading the parameter
+    /// columns with their `names`
+    pub fn write_csv(&self, path: &Path, names: &[String; N]) -> Result<(), Box<dyn Error>> {
+        let mut writer = Writer::from_path(path)?;
+        let mut header = vec!["iteration".to_string(), "error".into(), "magnitude".into()];
+        header.extend(names.iter().cloned());
+        writer.write_record(header)?;
+        for entry in &self.entries {
+            let mut record = vec![
+                entry.iteration.to_string(),
+                entry.error.to_string(),
+                entry.magnitude.to_string(),
+            ];
+            record.extend(entry.parameters.iter().map(f64::to_string));
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, History};
+
+    #[test]
+    fn thins_out() {
+        let mut history = History::<1>::default();
+        let count = 3 * History::<1>::CAPACITY;
+        for iteration in 0..count {
+            let entry = Entry {
+                iteration,
+                error: 0.0,
+                magnitude: 0.0,
+                parameters: [0.0],
+            };
+            history.record(entry, iteration == count - 1);
+        }
+        assert!(history.entries.len() <= History::<1>::CAPACITY);
+        assert_eq!(history.entries[0].iteration, 0);
+        assert_eq!(history.entries.last().unwrap().iteration, count - 1);
+        let stride = history.entries[1].iteration;
+        assert!(history
+            .entries
+            .windows(2)
+            .take(history.entries.len() - 2)
+            .all(|pair| pair[1].iteration - pair[0].iteration == stride));
+    }
+}
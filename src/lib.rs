@@ -0,0 +1,356 @@
+#![cfg_attr(
+    feature = "nightly",
+    allow(incomplete_features),
+    feature(generic_const_exprs)
+)]
+//! Fitting models to data by least squares, robust losses or gradient descent, with the
+//! statistics and plots to judge the fit. Implement [`GradientDescent`] for a model, or use one of
+//! the [`regressors`], and pass it to [`fit`]. Models whose size is only known at runtime
+//! implement [`dynamic::Regressor`] instead, which also works on stable Rust without the
+//! `nightly` feature
+#[cfg(feature = "nightly")]
+use std::array;
+use std::{error::Error, fmt};
+
+pub use crate::data::Record;
+#[cfg(feature = "nightly")]
+pub use crate::fitting::{fit, fit_with, FitOptions, FitResult, Iteration, Solver};
+use crate::{dual::Scalar, expression::Expression, losses::Loss};
+
+pub mod data;
+pub mod dual;
+pub mod dynamic;
+pub mod expression;
+#[cfg(feature = "nightly")]
+pub mod fitting;
+pub mod functions;
+pub mod history;
+pub mod json;
+mod linalg;
+pub mod losses;
+pub mod optimizers;
+pub mod pdf;
+pub mod plots;
+#[cfg(feature = "nightly")]
+pub mod regressors;
+pub mod saved;
+pub mod solvers;
+pub mod statistics;
+
+#[cfg(feature = "nightly")]
+/// Model whose parameters can be fit to data, taking `IN_DIMENSION` inputs to `OUT_DIMENSION`
+/// outputs through `PARAM_DIMENSION` parameters
+pub trait GradientDescent {
+    const IN_DIMENSION: usize;
+    const PARAM_DIMENSION: usize;
+    const OUT_DIMENSION: usize;
+    /// Whether predictions are linear in the parameters, so that least squares fits can be found
+    /// exactly with a single solve
+    const LINEAR: bool = false;
+
+    fn predict(
+        &self,
+        nudge: Option<(usize, f64)>,
+        input: &[f64; Self::IN_DIMENSION],
+    ) -> [f64; Self::OUT_DIMENSION];
+    /// Current parameter values, in the same order as the adjustments passed to `descend`
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION];
+    /// Overwrite every parameter, in the order of `parameters`
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]);
+    fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]);
+
+    /// Name of each parameter, in the order of `parameters`. Defaults to `p0`, `p1`, ...
+    fn parameter_names(&self) -> [String; Self::PARAM_DIMENSION] {
+        array::from_fn(|i| format!("p{i}"))
+    }
+
+    /// Partial derivatives of each output with respect to each parameter at `input`. Defaults to
+    /// a finite difference estimate that nudges each parameter by `epsilon`; regressors that know
+    /// their derivatives exactly should override it
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        finite_difference_jacobian(self, input, epsilon)
+    }
+}
+
+#[cfg(feature = "nightly")]
+fn finite_difference_jacobian<R>(
+    regressor: &R,
+    input: &[f64; R::IN_DIMENSION],
+    epsilon: f64,
+) -> [[f64; R::PARAM_DIMENSION]; R::OUT_DIMENSION]
+where
+    R: GradientDescent + ?Sized,
+{
+    let base = regressor.predict(None, input);
+    let nudged: [[f64; R::OUT_DIMENSION]; R::PARAM_DIMENSION] =
+        array::from_fn(|nudge| regressor.predict(Some((nudge, epsilon)), input));
+    array::from_fn(|out| array::from_fn(|param| (nudged[param][out] - base[out]) / epsilon))
+}
+
+pub trait Magnitude {
+    fn magnitude(&self) -> f64;
+}
+
+impl<const N: usize> Magnitude for [f64; N] {
+    fn magnitude(&self) -> f64 {
+        match N {
+            0 => 0.0,
+            1 => self[0],
+            2 => self[0].hypot(self[1]),
+            _ => self.iter().map(|x| x * x).sum::<f64>().sqrt(),
+        }
+    }
+}
+
+#[cfg(feature = "nightly")]
+/// Jacobian of the regressor at `input`, estimated with finite differences if requested even when
+/// the regressor knows its derivatives exactly
+pub fn model_jacobian<R>(
+    regressor: &R,
+    input: &[f64; R::IN_DIMENSION],
+    finite_differences: bool,
+    epsilon: f64,
+) -> [[f64; R::PARAM_DIMENSION]; R::OUT_DIMENSION]
+where
+    R: GradientDescent,
+{
+    if finite_differences {
+        finite_difference_jacobian(regressor, input, epsilon)
+    } else {
+        regressor.jacobian(input, epsilon)
+    }
+}
+
+#[cfg(feature = "nightly")]
+/// Mean loss over each output of the data, weighted by the datum's weight for that output and
+/// the output's weight, and its gradient with respect to each parameter, pointing downhill
+fn error_gradients<R>(
+    regressor: &R,
+    data: &[Record<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>],
+    loss: Loss,
+    output_weights: &[f64],
+    finite_differences: bool,
+    epsilon: f64,
+) -> (f64, [f64; R::PARAM_DIMENSION])
+where
+    R: GradientDescent,
+    [(); R::IN_DIMENSION]:,
+    [(); R::PARAM_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    let mut base_error = 0.0;
+    let mut gradients = [0.0; R::PARAM_DIMENSION];
+    for datum in data {
+        let prediction = regressor.predict(None, &datum.x);
+        let jacobian = model_jacobian(regressor, &datum.x, finite_differences, epsilon);
+        base_error += weighted_loss(
+            loss,
+            &datum.y,
+            &datum.weight,
+            output_weights,
+            &prediction,
+            &jacobian,
+            &mut gradients,
+        );
+    }
+    let total_weight = total_weight(data, output_weights);
+    (
+        base_error / total_weight,
+        gradients.map(|gradient| gradient / total_weight),
+    )
+}
+
+#[cfg(feature = "nightly")]
+fn total_weight<const N: usize, const M: usize>(
+    data: &[Record<N, M>],
+    output_weights: &[f64],
+) -> f64 {
+    weight_sum(
+        data.iter().map(|datum| datum.weight.as_slice()),
+        output_weights,
+    )
+}
+
+#[cfg(feature = "nightly")]
+/// Residual of each output of the regressor at each datum, datum by datum, and the jacobian of
+/// that output there, both scaled by the square root of the datum's weight for the output and the
+/// output's weight
+pub fn residuals_and_jacobian<R>(
+    regressor: &R,
+    data: &[Record<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>],
+    output_weights: &[f64],
+    finite_differences: bool,
+    epsilon: f64,
+) -> (Vec<f64>, Vec<[f64; R::PARAM_DIMENSION]>)
+where
+    R: GradientDescent,
+    [(); R::IN_DIMENSION]:,
+    [(); R::PARAM_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    let mut residuals = Vec::with_capacity(data.len() * R::OUT_DIMENSION);
+    let mut rows = Vec::with_capacity(data.len() * R::OUT_DIMENSION);
+    for datum in data {
+        let prediction = regressor.predict(None, &datum.x);
+        let mut jacobian = model_jacobian(regressor, &datum.x, finite_differences, epsilon);
+        weigh_residuals(
+            &datum.y,
+            &datum.weight,
+            output_weights,
+            &prediction,
+            &mut jacobian,
+            &mut residuals,
+        );
+        rows.extend(jacobian);
+    }
+    (residuals, rows)
+}
+
+// The helpers below work on the slices of a single datum, so that models of fixed and of runtime
+// size share them
+
+/// Sum of the weights of every output of the data, each times the output's weight
+fn weight_sum<'a>(weights: impl IntoIterator<Item = &'a [f64]>, output_weights: &[f64]) -> f64 {
+    weights
+        .into_iter()
+        .flat_map(|weights| weights.iter().zip(output_weights).map(|(w, o)| w * o))
+        .sum()
+}
+
+/// Append the residual of each output `y` of a datum from its `prediction` to `residuals`, scaling
+/// it and the output's row of the `jacobian` by the square root of the datum's weight for the
+/// output and the output's weight
+fn weigh_residuals<Row: AsMut<[f64]>>(
+    y: &[f64],
+    weight: &[f64],
+    output_weights: &[f64],
+    prediction: &[f64],
+    jacobian: &mut [Row],
+    residuals: &mut Vec<f64>,
+) {
+    for (k, row) in jacobian.iter_mut().enumerate() {
+        let scale = (weight[k] * output_weights[k]).sqrt();
+        residuals.push((y[k] - prediction[k]) * scale);
+        for derivative in row.as_mut() {
+            *derivative *= scale;
+        }
+    }
+}
+
+/// Weighted sum of the squared errors of the outputs of a datum
+fn weighted_squared_error(
+    y: &[f64],
+    weight: &[f64],
+    output_weights: &[f64],
+    prediction: &[f64],
+) -> f64 {
+    (0..y.len())
+        .map(|k| {
+            let delta = y[k] - prediction[k];
+            weight[k] * output_weights[k] * delta * delta
+        })
+        .sum()
+}
+
+/// Weighted loss of the outputs of a datum, adding its downhill gradient along each output's row
+/// of the `jacobian` to `gradients`
+fn weighted_loss<Row: AsRef<[f64]>>(
+    loss: Loss,
+    y: &[f64],
+    weight: &[f64],
+    output_weights: &[f64],
+    prediction: &[f64],
+    jacobian: &[Row],
+    gradients: &mut [f64],
+) -> f64 {
+    let mut error = 0.0;
+    for (k, row) in jacobian.iter().enumerate() {
+        let weight = weight[k] * output_weights[k];
+        error += weight * loss.value(y[k], prediction[k]);
+        let slope = weight * loss.derivative(y[k], prediction[k]);
+        for (gradient, derivative) in gradients.iter_mut().zip(row.as_ref()) {
+            *gradient -= slope * derivative;
+        }
+    }
+    error
+}
+
+/// Weight of the error in each of a model's `outputs`: those `given`, checking there is one for
+/// each, or 1 for every output if none are
+fn output_weights(given: &[f64], outputs: usize) -> Result<Vec<f64>, Box<dyn Error>> {
+    let weights = match given.len() {
+        0 => vec![1.0; outputs],
+        n if n == outputs => given.to_vec(),
+        n => {
+            return Err(format!(
+                "the model has {outputs} outputs, but {n} output weights were given"
+            )
+            .into())
+        }
+    };
+    match weights
+        .iter()
+        .find(|weight| !(**weight >= 0.0 && weight.is_finite()))
+    {
+        Some(weight) => Err(format!("output weight {weight} is not a nonnegative number").into()),
+        None => Ok(weights),
+    }
+}
+
+/// Write a polynomial with `terms` as its coefficients, constant term first, highest power first
+pub(crate) fn write_polynomial(f: &mut fmt::Formatter<'_>, terms: &[f64]) -> fmt::Result {
+    for (i, constant) in terms.iter().enumerate().rev() {
+        let sign = match (i == terms.len() - 1, constant.is_sign_positive()) {
+            (true, true) => "",
+            (true, false) => "-",
+            (false, true) => " + ",
+            (false, false) => " - ",
+        };
+        let term = match i {
+            0 => "".to_string(),
+            1 => "x".to_string(),
+            n => format!("x^{n}"),
+        };
+        write!(f, "{sign}{}{term}", constant.abs())?;
+    }
+    Ok(())
+}
+
+/// Value at `x` of a polynomial with `terms` as its coefficients, constant term first
+pub(crate) fn evaluate_polynomial<S: Scalar>(terms: impl IntoIterator<Item = S>, x: f64) -> S {
+    terms
+        .into_iter()
+        .enumerate()
+        .fold(S::constant(0.0), |sum, (i, constant)| {
+            sum + constant * x.powi(i as i32)
+        })
+}
+
+/// Derivatives at `x` of a polynomial of `terms` coefficients with respect to each of them
+pub(crate) fn polynomial_derivatives(terms: usize, x: f64) -> impl Iterator<Item = f64> {
+    (0..terms).map(move |i| x.powi(i as i32))
+}
+
+/// Increase the `nudge.0`th parameter by `nudge.1`, if there is a nudge
+pub(crate) fn apply_nudge(parameters: &mut [f64], nudge: Option<(usize, f64)>) {
+    if let Some((i, epsilon)) = nudge {
+        parameters[i] += epsilon;
+    }
+}
+
+/// Write the outputs of `expressions`, separated by `;`, with `parameters` substituted in
+pub(crate) fn write_formula(
+    f: &mut fmt::Formatter<'_>,
+    expressions: &[Expression],
+    parameters: &[f64],
+) -> fmt::Result {
+    let outputs = expressions
+        .iter()
+        .map(|expression| expression.substitute(parameters))
+        .collect::<Vec<_>>();
+    write!(f, "{}", outputs.join("; "))
+}
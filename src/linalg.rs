@@ -0,0 +1,203 @@
+/// Solve `matrix * x = vector` by Gaussian elimination with partial pivoting, returning `None`
+/// if the matrix is singular
+pub fn solve<const N: usize>(mut matrix: [[f64; N]; N], mut vector: [f64; N]) -> Option<[f64; N]> {
+    eliminate(&mut matrix, &mut vector).map(|solution| std::array::from_fn(|i| solution[i]))
+}
+
+/// [`solve`] for a matrix whose size is only known at runtime
+pub fn solve_dynamic(mut matrix: Vec<Vec<f64>>, mut vector: Vec<f64>) -> Option<Vec<f64>> {
+    eliminate(&mut matrix, &mut vector)
+}
+
+/// [`solve`] for rows of any storage, which are left reduced to upper triangular form
+fn eliminate<Row>(matrix: &mut [Row], vector: &mut [f64]) -> Option<Vec<f64>>
+where
+    Row: AsRef<[f64]> + AsMut<[f64]>,
+{
+    let n = vector.len();
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&a, &b| {
+            let (a, b) = (matrix[a].as_ref()[column], matrix[b].as_ref()[column]);
+            a.abs().total_cmp(&b.abs())
+        })?;
+        let pivot_value = matrix[pivot].as_ref()[column];
+        if pivot_value == 0.0 || !pivot_value.is_finite() {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        vector.swap(column, pivot);
+        let (above, below) = matrix.split_at_mut(column + 1);
+        let pivot_row = above[column].as_ref();
+        for (row, index) in below.iter_mut().zip(column + 1..) {
+            let row = row.as_mut();
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot_value) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot_value;
+            }
+            vector[index] -= factor * vector[column];
+        }
+    }
+    let mut solution = vec![0.0; n];
+    for row in (0..n).rev() {
+        let coefficients = matrix[row].as_ref();
+        let known: f64 = (row + 1..n).map(|k| coefficients[k] * solution[k]).sum();
+        solution[row] = (vector[row] - known) / coefficients[row];
+    }
+    Some(solution)
+}
+
+/// Inverse of a square matrix, found by solving for each column of the identity, or `None` if
+/// the matrix is singular
+pub fn invert<const N: usize>(matrix: [[f64; N]; N]) -> Option<[[f64; N]; N]> {
+    let mut inverse = [[0.0; N]; N];
+    for column in 0..N {
+        let unit = std::array::from_fn(|i| if i == column { 1.0 } else { 0.0 });
+        let solution = solve(matrix, unit)?;
+        for (row, value) in inverse.iter_mut().zip(solution) {
+            row[column] = value;
+        }
+    }
+    Some(inverse)
+}
+
+/// [`invert`] for a matrix whose size is only known at runtime
+pub fn invert_dynamic(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut inverse = vec![vec![0.0; n]; n];
+    for column in 0..n {
+        let unit = (0..n)
+            .map(|i| if i == column { 1.0 } else { 0.0 })
+            .collect();
+        let solution = solve_dynamic(matrix.to_vec(), unit)?;
+        for (row, value) in inverse.iter_mut().zip(solution) {
+            row[column] = value;
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(feature = "nightly")]
+/// Find `x` minimizing `|rows * x - targets|` by Householder QR decomposition, returning `None` if
+/// the columns are linearly dependent. Columns are scaled to unit length first so that badly
+/// scaled problems, such as high degree polynomials, stay well conditioned
+pub fn least_squares<const N: usize>(
+    mut rows: Vec<[f64; N]>,
+    mut targets: Vec<f64>,
+) -> Option<[f64; N]> {
+    if rows.len() < N {
+        return None;
+    }
+    let scales: [f64; N] = std::array::from_fn(|j| {
+        let norm = rows.iter().map(|row| row[j] * row[j]).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            norm
+        } else {
+            1.0
+        }
+    });
+    for row in rows.iter_mut() {
+        for (value, scale) in row.iter_mut().zip(scales) {
+            *value /= scale;
+        }
+    }
+
+    // reduce `rows` to upper triangular form, applying the same reflections to `targets`
+    for k in 0..N {
+        let norm = rows[k..]
+            .iter()
+            .map(|row| row[k] * row[k])
+            .sum::<f64>()
+            .sqrt();
+        if norm < 1e-12 {
+            return None;
+        }
+        let alpha = if rows[k][k] > 0.0 { -norm } else { norm };
+        let mut reflector = rows[k..].iter().map(|row| row[k]).collect::<Vec<_>>();
+        reflector[0] -= alpha;
+        let reflector_norm = reflector.iter().map(|v| v * v).sum::<f64>();
+        if reflector_norm == 0.0 {
+            continue;
+        }
+        for j in k..N {
+            let dot: f64 = reflector
+                .iter()
+                .zip(&rows[k..])
+                .map(|(v, row)| v * row[j])
+                .sum();
+            let factor = 2.0 * dot / reflector_norm;
+            for (v, row) in reflector.iter().zip(&mut rows[k..]) {
+                row[j] -= factor * v;
+            }
+        }
+        let dot: f64 = reflector
+            .iter()
+            .zip(&targets[k..])
+            .map(|(v, t)| v * t)
+            .sum();
+        let factor = 2.0 * dot / reflector_norm;
+        for (v, target) in reflector.iter().zip(&mut targets[k..]) {
+            *target -= factor * v;
+        }
+    }
+
+    let mut solution = [0.0; N];
+    for row in (0..N).rev() {
+        let known: f64 = (row + 1..N).map(|k| rows[row][k] * solution[k]).sum();
+        solution[row] = (targets[row] - known) / rows[row][row];
+    }
+    Some(std::array::from_fn(|j| solution[j] / scales[j]))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "nightly")]
+    use super::least_squares;
+    use super::{invert, invert_dynamic, solve, solve_dynamic};
+
+    #[test]
+    fn solves() {
+        let matrix = [[0.0, 2.0, 1.0], [1.0, 1.0, 1.0], [4.0, -1.0, 3.0]];
+        let solution = solve(matrix, [7.0, 6.0, 11.0]).unwrap();
+        for (value, expected) in solution.into_iter().zip([1.0, 2.0, 3.0]) {
+            assert!((value - expected).abs() < 1e-12);
+        }
+        assert!(solve([[1.0, 2.0], [2.0, 4.0]], [1.0, 2.0]).is_none());
+
+        let rows = matrix.iter().map(|row| row.to_vec()).collect();
+        assert_eq!(solve_dynamic(rows, vec![7.0, 6.0, 11.0]).unwrap(), solution);
+    }
+
+    #[test]
+    fn inverts() {
+        let inverse = invert([[4.0, 7.0], [2.0, 6.0]]).unwrap();
+        let expected = [[0.6, -0.7], [-0.2, 0.4]];
+        for (row, expected_row) in inverse.into_iter().zip(expected) {
+            for (value, expected) in row.into_iter().zip(expected_row) {
+                assert!((value - expected).abs() < 1e-12);
+            }
+        }
+        assert!(invert([[1.0, 2.0], [2.0, 4.0]]).is_none());
+        let rows = [vec![4.0, 7.0], vec![2.0, 6.0]];
+        let dynamic = invert_dynamic(&rows).unwrap();
+        assert_eq!(dynamic, inverse.map(|row| row.to_vec()));
+        assert!(invert_dynamic(&[vec![1.0, 2.0], vec![2.0, 4.0]]).is_none());
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn fits_polynomial() {
+        let terms = [4.0, -3.0, 0.5, 2e-3, -1e-5, 3e-8];
+        let (rows, targets) = (0..200)
+            .map(|i| {
+                let x = i as f64;
+                let row: [f64; 6] = std::array::from_fn(|j| x.powi(j as i32));
+                let y = row.iter().zip(terms).map(|(a, b)| a * b).sum::<f64>();
+                (row, y)
+            })
+            .unzip();
+        let solution = least_squares(rows, targets).unwrap();
+        for (value, expected) in solution.into_iter().zip(terms) {
+            assert!((value - expected).abs() < 1e-6 * expected.abs());
+        }
+    }
+}
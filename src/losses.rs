@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+/// Penalty for the difference between an observed value and the model's prediction of it. The
+/// total loss of a fit is the mean of the loss over every datum. Losses that are quadratic for
+/// small errors equal the squared error `r²` there, rather than `r² / 2`, so that they compare
+/// with it and descend at the same learning rate
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Loss {
+    /// Squared error
+    Squared,
+    /// Absolute error
+    Absolute,
+    /// Squared for errors smaller than `delta`, `2 delta |r| - delta²` beyond it
+    Huber { delta: f64 },
+    /// `scale² ln(1 + (r / scale)²)`, with errors much larger than `scale` counting very little
+    Cauchy { scale: f64 },
+    /// `2 ln(cosh(r))`; squared near zero, twice the absolute error far from it
+    LogCosh,
+    /// Asymmetric absolute error, fitting the `tau` quantile of the data
+    Quantile { tau: f64 },
+    /// Poisson deviance, for count data; predictions must be positive
+    Poisson,
+}
+
+impl Loss {
+    /// Loss of predicting `prediction` for the observed value `observed`
+    pub fn value(self, observed: f64, prediction: f64) -> f64 {
+        let residual = observed - prediction;
+        match self {
+            Loss::Squared => residual * residual,
+            Loss::Absolute => residual.abs(),
+            Loss::Huber { delta } => {
+                if residual.abs() <= delta {
+                    residual * residual
+                } else {
+                    delta * (2.0 * residual.abs() - delta)
+                }
+            }
+            Loss::Cauchy { scale } => {
+                let scaled = residual / scale;
+                scale * scale * scaled.mul_add(scaled, 1.0).ln()
+            }
+            // ln(cosh(r)) rewritten so that it doesn't overflow for large residuals
+            Loss::LogCosh => {
+                let log_cosh =
+                    residual.abs() + (-2.0 * residual.abs()).exp().ln_1p() - std::f64::consts::LN_2;
+                2.0 * log_cosh
+            }
+            Loss::Quantile { tau } => residual * if residual < 0.0 { tau - 1.0 } else { tau },
+            Loss::Poisson => {
+                let observed_term = if observed == 0.0 {
+                    0.0
+                } else {
+                    observed * (observed / prediction).ln()
+                };
+                2.0 * (observed_term - residual)
+            }
+        }
+    }
+
+    /// Derivative of the loss with respect to the prediction
+    pub fn derivative(self, observed: f64, prediction: f64) -> f64 {
+        let residual = observed - prediction;
+        match self {
+            Loss::Squared => -2.0 * residual,
+            Loss::Absolute => {
+                if residual == 0.0 {
+                    0.0
+                } else {
+                    -residual.signum()
+                }
+            }
+            Loss::Huber { delta } => -2.0 * residual.clamp(-delta, delta),
+            Loss::Cauchy { scale } => {
+                let scaled = residual / scale;
+                -2.0 * residual / scaled.mul_add(scaled, 1.0)
+            }
+            Loss::LogCosh => -2.0 * residual.tanh(),
+            Loss::Quantile { tau } => {
+                if residual < 0.0 {
+                    1.0 - tau
+                } else {
+                    -tau
+                }
+            }
+            Loss::Poisson => 2.0 * (1.0 - observed / prediction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Loss;
+
+    #[test]
+    fn derivatives() {
+        let losses = [
+            Loss::Squared,
+            Loss::Absolute,
+            Loss::Huber { delta: 1.5 },
+            Loss::Cauchy { scale: 2.0 },
+            Loss::LogCosh,
+            Loss::Quantile { tau: 0.2 },
+            Loss::Poisson,
+        ];
+        let epsilon = 1e-6;
+        for loss in losses {
+            for (observed, prediction) in [(3.0, 1.0), (1.0, 3.5), (4.0, 3.9), (0.0, 0.5)] {
+                let estimate = (loss.value(observed, prediction + epsilon)
+                    - loss.value(observed, prediction - epsilon))
+                    / (2.0 * epsilon);
+                let derivative = loss.derivative(observed, prediction);
+                assert!(
+                    (estimate - derivative).abs() < 1e-6,
+                    "{loss:?} at ({observed}, {prediction}): {estimate} != {derivative}"
+                );
+            }
+            assert!(loss.value(2.0, 2.0).abs() < 1e-15, "{loss:?}");
+        }
+    }
+
+    #[test]
+    fn quadratic_like_squared_error() {
+        // near zero the robust losses are the squared error itself
+        let robust = [
+            Loss::Huber { delta: 1.5 },
+            Loss::Cauchy { scale: 2.0 },
+            Loss::LogCosh,
+        ];
+        let squared = Loss::Squared.value(1.0, 1.01);
+        for loss in robust {
+            let value = loss.value(1.0, 1.01);
+            assert!((value / squared - 1.0).abs() < 1e-4, "{loss:?}: {value}");
+        }
+        assert_eq!(Loss::Huber { delta: 1.5 }.value(0.0, 1.0), 1.0);
+        assert_eq!(Loss::Huber { delta: 1.5 }.value(0.0, 3.0), 6.75);
+    }
+}
@@ -1,67 +1,246 @@
 #![allow(incomplete_features)]
 #![feature(generic_const_exprs)]
-use csv::Reader;
 use std::{
     array,
     error::Error,
-    fs::{create_dir, create_dir_all},
-    path::PathBuf,
+    fmt::Display,
+    fs::{create_dir_all, File},
+    io::{self, Write},
+    iter,
+    ops::Range,
+    path::{Path, PathBuf},
+    process::ExitCode,
     time::Duration,
 };
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use progress_observer::Observer;
 
-use plotters::prelude::*;
-// use plotters::style::full_palette::*;
+use plotters::style::RGBColor;
 
-use crate::regressors::{
-    Exponential, Linear, ParametricScaledTranslatedEquation, Polynomial, ScaledTranslatedEquation,
+use regression::{
+    data::{load, parse_byte, Column, CsvOptions, Record},
+    dual::Dual,
+    dynamic::{self, Fixed, Regressor, Sample},
+    expression::Expression,
+    fit_with,
+    functions::Function,
+    losses::Loss,
+    model_jacobian,
+    optimizers::{Adagrad, Adam, Momentum, Optimizer, RmsProp, Sgd},
+    plots::{
+        grid, parse_color, save, Animation, Band, Convergence, Figure, FitPlot, Panels, ParityPlot,
+        PlotFormat, PlotStyle, ResidualDiagnostics, SurfacePlot,
+    },
+    regressors::{
+        Exponential, Formula, Linear, MultiLinear, ParametricEquation,
+        ParametricScaledTranslatedEquation, Polynomial, ScaledTranslatedEquation,
+    },
+    residuals_and_jacobian,
+    saved::{FitMetadata, ModelSpec, Parameter, SavedCovariance, SavedModel},
+    statistics::{Covariance, DynamicCovariance, FitReport},
+    FitOptions, GradientDescent, Iteration, Solver,
 };
 
-mod regressors;
-
-#[derive(serde::Deserialize)]
-struct Record(f64, f64);
-
-trait GradientDescent {
-    const IN_DIMENSION: usize;
-    const PARAM_DIMENSION: usize;
-    const OUT_DIMENSION: usize;
-
-    fn predict(
-        &self,
-        nudge: Option<(usize, f64)>,
-        input: &[f64; Self::IN_DIMENSION],
-    ) -> [f64; Self::OUT_DIMENSION];
-    fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]);
+/// Regressor to fit to the data
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Model {
+    /// slope * x + y_intercept
+    Linear,
+    /// w0 * x0 + w1 * x1 + ... + intercept, with an input for each `--x-column`
+    MultiLinear,
+    /// base * growth ^ x
+    Exponential,
+    /// Polynomial of degree `--degree`, constant term first
+    Polynomial,
+    /// f((x - x_0) / width) * height + y_0, where f is `--function`
+    ScaledTranslated,
+    /// f(x, p...), where f is `--function`
+    Parametric,
+    /// f((x - x_0) / width, p...) * height + y_0, where f is `--function`
+    ParametricScaledTranslated,
+    /// Formula given by `--expression`
+    Expression,
 }
 
-trait Magnitude {
-    fn magnitude(&self) -> f64;
+/// Loss minimized by the fit, averaged over the data
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LossKind {
+    /// Mean squared error
+    Mse,
+    /// Mean absolute error
+    Mae,
+    /// Squared for small errors and absolute for large ones, switching at `--loss-scale`
+    Huber,
+    /// Cauchy (Lorentzian) loss with scale `--loss-scale`; barely penalizes gross outliers
+    Cauchy,
+    /// Twice the logarithm of the hyperbolic cosine of the error
+    LogCosh,
+    /// Pinball loss, fitting the `--quantile` quantile of the data
+    Quantile,
+    /// Poisson deviance, for count data
+    Poisson,
 }
 
-impl<const N: usize> Magnitude for [f64; N] {
-    fn magnitude(&self) -> f64 {
-        match N {
-            0 => 0.0,
-            1 => self[0],
-            2 => self[0].hypot(self[1]),
-            _ => self.iter().map(|x| x * x).sum::<f64>().sqrt(),
-        }
-    }
+/// Rule for turning gradients into parameter adjustments
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OptimizerKind {
+    /// Plain gradient descent
+    Sgd,
+    /// Gradient descent with momentum
+    Momentum,
+    /// Gradient descent with Nesterov momentum
+    Nesterov,
+    /// Learning rates adapted by the sum of past squared gradients
+    Adagrad,
+    /// Learning rates adapted by a decaying average of squared gradients
+    Rmsprop,
+    /// Adaptive moment estimation
+    Adam,
 }
 
 #[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// CSV File with data to regress on
-    data_file: PathBuf,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// CSV File with data to regress on, or - to read it from standard input
+    #[clap(required = true)]
+    data_file: Option<PathBuf>,
+
+    /// Comma separated columns holding the inputs, by header or by position counting from 0
+    #[clap(long, value_delimiter = ',', default_value = "0")]
+    x_column: Vec<Column>,
+
+    /// Comma separated columns holding the observed value of each output of the model, by header
+    /// or by position counting from 0. Defaults to the columns after the inputs, assuming they
+    /// come first
+    #[clap(long, value_delimiter = ',')]
+    y_column: Vec<Column>,
+
+    /// Comma separated weight of the error in each output of the model
+    #[clap(long, value_delimiter = ',')]
+    output_weights: Vec<f64>,
+
+    /// Column holding the weight of each observation in the fit, or comma separated columns
+    /// holding the weight of each output
+    #[clap(long, value_delimiter = ',', conflicts_with = "sigma_column")]
+    weight_column: Vec<Column>,
+
+    /// Column holding the standard deviation of each observation, weighting it by 1/σ² in the
+    /// fit and drawn as error bars, or comma separated columns holding that of each output
+    #[clap(long, value_delimiter = ',')]
+    sigma_column: Vec<Column>,
+
+    /// Character separating the fields of the CSV file; \t for tabs
+    #[clap(long, default_value = ",", value_parser = parse_byte)]
+    delimiter: u8,
+
+    /// Character quoting fields of the CSV file
+    #[clap(long, default_value = "\"", value_parser = parse_byte)]
+    quote: u8,
+
+    /// Skip lines of the CSV file starting with this character
+    #[clap(long, value_parser = parse_byte)]
+    comment: Option<u8>,
+
+    /// Treat the first row of the CSV file as data rather than column headers
+    #[clap(long)]
+    no_headers: bool,
 
-    /// Temperature of regression; higher = faster learning, but more chaotic
+    /// Model to fit to the data
+    #[clap(short, long, value_enum, default_value_t = Model::Linear)]
+    model: Model,
+
+    /// Degree of the polynomial model
+    #[clap(short, long, default_value_t = 2)]
+    degree: usize,
+
+    /// Function used by the equation models
+    #[clap(long, value_enum, default_value_t = Function::Gaussian)]
+    function: Function,
+
+    /// Formula for the expression model, in terms of x and named parameters, e.g.
+    /// `a * exp(-b * x) + c`. Supports + - * / ^ and exp, ln, sin, cos, tan, tanh, abs, sqrt, pow.
+    /// Several expressions separated by `;` make a model with an output for each, sharing their
+    /// parameters, e.g. `x0 + v * x; y0 - g / 2 * x ^ 2`
+    #[clap(short = 'x', long, allow_hyphen_values = true)]
+    expression: Option<String>,
+
+    /// Comma separated initial parameter values, in the order the model displays them
+    #[clap(short, long, value_delimiter = ',', allow_hyphen_values = true)]
+    initial: Vec<f64>,
+
+    /// Continue from a model saved with `--save-model`, fitting it again starting from its
+    /// parameters, instead of the model given by `--model`
+    #[clap(long, conflicts_with_all = ["model", "degree", "function", "expression", "initial"])]
+    load_model: Option<PathBuf>,
+
+    /// Save the fitted model, with how it was fit, to this JSON file
+    #[clap(long)]
+    save_model: Option<PathBuf>,
+
+    /// Temperature of regression; higher = faster learning, but more chaotic. Serves as the
+    /// learning rate of every optimizer
     #[clap(short, long, default_value_t = 1e-10)]
     temperature: f64,
 
-    /// Epsilon value in derivative for computing gradients. should be a low value
+    /// Method used to fit the model
+    #[clap(long, value_enum, default_value_t = Solver::Auto)]
+    solver: Solver,
+
+    /// Initial damping factor of the Levenberg-Marquardt solver
+    #[clap(long, default_value_t = 1e-3)]
+    damping: f64,
+
+    /// Loss to minimize
+    #[clap(long, value_enum, default_value_t = LossKind::Mse)]
+    loss: LossKind,
+
+    /// Scale of the error at which the Huber and Cauchy losses stop growing quadratically
+    #[clap(long, default_value_t = 1.0)]
+    loss_scale: f64,
+
+    /// Quantile fit by the quantile loss, between 0 and 1
+    #[clap(long, default_value_t = 0.5)]
+    quantile: f64,
+
+    /// Probability covered by the reported parameter confidence intervals
+    #[clap(long, default_value_t = 0.95)]
+    confidence: f64,
+
+    /// Optimizer used to descend the gradient
+    #[clap(long, value_enum, default_value_t = OptimizerKind::Sgd)]
+    optimizer: OptimizerKind,
+
+    /// Fraction of the previous velocity kept each iteration by the momentum optimizers
+    #[clap(long, default_value_t = 0.9)]
+    momentum: f64,
+
+    /// Decay rate of the squared gradient average used by RMSProp
+    #[clap(long, default_value_t = 0.9)]
+    decay: f64,
+
+    /// Decay rate of the gradient average used by Adam
+    #[clap(long, default_value_t = 0.9)]
+    beta1: f64,
+
+    /// Decay rate of the squared gradient average used by Adam
+    #[clap(long, default_value_t = 0.999)]
+    beta2: f64,
+
+    /// Small value added to the denominators of the adaptive optimizers to avoid dividing by zero
+    #[clap(long, default_value_t = 1e-8)]
+    stabilizer: f64,
+
+    /// Estimate gradients with finite differences even for models whose derivatives are known
+    /// exactly
+    #[clap(long)]
+    finite_differences: bool,
+
+    /// Epsilon value in derivative for computing gradients with finite differences. should be a
+    /// low value
     #[clap(short, long, default_value_t = 1e-8)]
     epsilon: f64,
 
@@ -74,177 +253,1211 @@ struct Args {
     print_interval: f64,
 
     /// Directory to output plots to
-    #[clap(short, long, default_value = "graphs")]
+    #[clap(short = 'o', long, default_value = "graphs")]
     plot_out: PathBuf,
+
+    /// File format of the plots. SVG and PDF are vector formats
+    #[clap(long, value_enum, default_value_t = PlotFormat::Png)]
+    plot_format: PlotFormat,
+
+    /// Width of the plots, in pixels
+    #[clap(long, default_value_t = 1920)]
+    width: u32,
+
+    /// Height of the plots, in pixels
+    #[clap(long, default_value_t = 1080)]
+    height: u32,
+
+    /// Color of the data points, as a name or hexadecimal #rrggbb
+    #[clap(long, default_value = "green", value_parser = parse_color)]
+    data_color: RGBColor,
+
+    /// Color of the fitted curve and its bands, as a name or hexadecimal #rrggbb
+    #[clap(long, default_value = "red", value_parser = parse_color)]
+    fit_color: RGBColor,
+
+    /// Radius of the plotted points, in pixels
+    #[clap(long, default_value_t = 2)]
+    point_size: u32,
+
+    /// Width of the plotted lines, in pixels
+    #[clap(long, default_value_t = 2)]
+    line_width: u32,
+
+    /// Title of the fit plot
+    #[clap(long)]
+    title: Option<String>,
+
+    /// Label of the x axis of the fit plot
+    #[clap(long, default_value = "x")]
+    x_label: String,
+
+    /// Label of the y axis of the fit plot
+    #[clap(long, default_value = "y")]
+    y_label: String,
+
+    /// Number of points the fitted curve is drawn through
+    #[clap(long, default_value_t = 500)]
+    samples: usize,
+
+    /// Number of cells along each side of the heatmap drawn for models of two inputs
+    #[clap(long, default_value_t = 100)]
+    surface_cells: usize,
+
+    /// Extend the fitted curve beyond the data on each side by this fraction of the data's width
+    #[clap(long, default_value_t = 0.0)]
+    extrapolate: f64,
+
+    /// Use a logarithmic x axis on the fit plot, hiding points with nonpositive x
+    #[clap(long)]
+    log_x: bool,
+
+    /// Use a logarithmic y axis on the fit plot, hiding points with nonpositive y
+    #[clap(long)]
+    log_y: bool,
+
+    /// Collect the fit plots into a single animated GIF instead of writing one file per print
+    #[clap(long)]
+    gif: bool,
+
+    /// Time each frame of the animated GIF is shown for, in milliseconds
+    #[clap(long, default_value_t = 200)]
+    frame_delay: u32,
+
+    /// Include the trajectory of each parameter in the convergence plot
+    #[clap(long)]
+    plot_parameters: bool,
+
+    /// Also write the error, gradient magnitude and parameters at each iteration to a CSV file
+    /// in the plot directory
+    #[clap(long)]
+    history_csv: bool,
 }
 
-// fn regress(args: &Args, regressor: &R, data: Vec<Record>)
-// where
-//     R: GradientDescent,
-// {
-//     for (i, should_print) in Observer::new_with(
-//         Duration::from_secs_f64(args.print_interval),
-//         progress_observer::Options {
-//             checkpoint_size: 5000,
-//             ..Default::default()
-//         },
-//     )
-//     .enumerate()
-//     {
-//         let base_error: f64 = data
-//             .iter()
-//             .map(|datum| {
-//                 let delta = datum.1 - regressor.predict(None, &[datum.0])[0];
-//                 delta * delta
-//             })
-//             .sum();
-//         let gradients = array::from_fn(|nudge| {
-//             let gradient_error: f64 = data
-//                 .iter()
-//                 .map(|datum| {
-//                     let delta =
-//                         datum.1 - regressor.predict(Some((nudge, args.epsilon)), &[datum.0])[0];
-//                     delta * delta
-//                 })
-//                 .sum();
-//             (base_error - gradient_error) / args.epsilon
-//         });
-//         let magnitude = gradients.magnitude();
-//         if magnitude.abs() <= args.finish_threshold {
-//             println!("Done after {i} iterations");
-//             break;
-//         }
-//         regressor.descend(gradients.map(|x| x * args.temperature));
-//         if should_print {
-//             println!("i: {i}, r: {regressor}, mag: {magnitude} err: {base_error}");
-//         }
-//     }
-// }
+impl Args {
+    /// File of the data to fit, which is only missing when running a subcommand
+    fn data_file(&self) -> &Path {
+        self.data_file
+            .as_deref()
+            .expect("the data file is required without a subcommand")
+    }
+}
 
-fn run(args: Args) -> Result<(), Box<dyn Error>> {
-    let mut reader = Reader::from_path(args.data_file)?;
-    let data = reader
-        .deserialize::<Record>()
-        .collect::<Result<Vec<_>, _>>()?;
+#[derive(Subcommand)]
+enum Command {
+    /// Predict with a model saved with `--save-model` at new inputs, writing them, the predicted
+    /// outputs and, if the model's parameters were identifiable, the prediction intervals of
+    /// each output as CSV. Models fit to weighted data get the confidence intervals of the mean
+    /// output instead, as new observations have no known weight
+    Predict(PredictArgs),
+}
+
+#[derive(clap::Args)]
+struct PredictArgs {
+    /// Model file written by `--save-model`
+    model: PathBuf,
+
+    /// CSV file with the inputs to predict at, or - to read them from standard input
+    #[clap(default_value = "-")]
+    data_file: PathBuf,
+
+    /// Comma separated columns holding the inputs, by header or by position counting from 0.
+    /// Defaults to the first columns, one for each input of the model
+    #[clap(long, value_delimiter = ',')]
+    x_column: Vec<Column>,
+
+    /// Character separating the fields of the CSV file; \t for tabs
+    #[clap(long, default_value = ",", value_parser = parse_byte)]
+    delimiter: u8,
+
+    /// Character quoting fields of the CSV file
+    #[clap(long, default_value = "\"", value_parser = parse_byte)]
+    quote: u8,
+
+    /// Skip lines of the CSV file starting with this character
+    #[clap(long, value_parser = parse_byte)]
+    comment: Option<u8>,
 
+    /// Treat the first row of the CSV file as data rather than column headers
+    #[clap(long)]
+    no_headers: bool,
+
+    /// Probability that a new observation falls within its prediction interval, or the mean
+    /// output within its confidence interval
+    #[clap(long, default_value_t = 0.95)]
+    confidence: f64,
+
+    /// Epsilon value in derivative for computing the intervals of models without exact
+    /// derivatives with finite differences
+    #[clap(short, long, default_value_t = 1e-8)]
+    epsilon: f64,
+
+    /// File to write the predictions to, or - for standard output
+    #[clap(short, long, default_value = "-")]
+    output: PathBuf,
+}
+
+fn build_optimizer<const N: usize>(args: &Args) -> Box<dyn Optimizer<N>> {
+    let learning_rate = args.temperature;
+    match args.optimizer {
+        OptimizerKind::Sgd => Box::new(Sgd { learning_rate }),
+        OptimizerKind::Momentum => Box::new(Momentum::new(learning_rate, args.momentum, false)),
+        OptimizerKind::Nesterov => Box::new(Momentum::new(learning_rate, args.momentum, true)),
+        OptimizerKind::Adagrad => Box::new(Adagrad::new(learning_rate, args.stabilizer)),
+        OptimizerKind::Rmsprop => {
+            Box::new(RmsProp::new(learning_rate, args.decay, args.stabilizer))
+        }
+        OptimizerKind::Adam => Box::new(Adam::new(
+            learning_rate,
+            args.beta1,
+            args.beta2,
+            args.stabilizer,
+        )),
+    }
+}
+
+fn build_loss(args: &Args) -> Result<Loss, Box<dyn Error>> {
+    Ok(match args.loss {
+        LossKind::Mse => Loss::Squared,
+        LossKind::Mae => Loss::Absolute,
+        LossKind::Huber => Loss::Huber {
+            delta: args.loss_scale,
+        },
+        LossKind::Cauchy => Loss::Cauchy {
+            scale: args.loss_scale,
+        },
+        LossKind::LogCosh => Loss::LogCosh,
+        LossKind::Quantile if !(0.0..=1.0).contains(&args.quantile) => {
+            return Err(format!("quantile {} is not between 0 and 1", args.quantile).into())
+        }
+        LossKind::Quantile => Loss::Quantile { tau: args.quantile },
+        LossKind::Poisson => Loss::Poisson,
+    })
+}
+
+/// Whether the data are weighted datum by datum, which leaves the weight, and so the
+/// uncertainty, of a new observation unknown
+fn weighted(args: &Args) -> bool {
+    !args.weight_column.is_empty() || !args.sigma_column.is_empty()
+}
+
+fn csv_options(args: &Args) -> CsvOptions {
+    CsvOptions {
+        x: args.x_column.clone(),
+        y: args.y_column.clone(),
+        weight: args.weight_column.clone(),
+        sigma: args.sigma_column.clone(),
+        delimiter: args.delimiter,
+        quote: args.quote,
+        comment: args.comment,
+        headers: !args.no_headers,
+    }
+}
+
+fn plot_style(args: &Args) -> PlotStyle {
+    PlotStyle {
+        format: args.plot_format,
+        size: (args.width, args.height),
+        data_color: args.data_color,
+        fit_color: args.fit_color,
+        point_size: args.point_size,
+        line_width: args.line_width,
+        title: args.title.clone(),
+        x_label: args.x_label.clone(),
+        y_label: args.y_label.clone(),
+        log_x: args.log_x,
+        log_y: args.log_y,
+    }
+}
+
+/// Range of x the fitted curve is drawn over: that of the data, extended on each side by
+/// `--extrapolate` times its width, measured on a logarithmic scale if the x axis is logarithmic
+fn curve_range<const N: usize, const M: usize>(args: &Args, data: &[Record<N, M>]) -> Range<f64> {
+    let xs = data
+        .iter()
+        .map(|record| record.x[0])
+        .filter(|x| !args.log_x || *x > 0.0)
+        .map(|x| if args.log_x { x.ln() } else { x });
+    let min = xs.clone().fold(f64::INFINITY, f64::min);
+    let max = xs.fold(f64::NEG_INFINITY, f64::max);
+    let margin = (max - min) * args.extrapolate;
+    let (start, end) = (min - margin, max + margin);
+    if args.log_x {
+        start.exp()..end.exp()
+    } else {
+        start..end
+    }
+}
+
+/// Range spanning `values`, zoomed out by a tenth so that none lie on its edges
+fn zoomed(values: impl Iterator<Item = f64> + Clone) -> Range<f64> {
     let fcmp = |a: &f64, b: &f64| a.total_cmp(b);
-    let min_x = data.iter().map(|r| r.0).min_by(fcmp).unwrap();
-    let max_x = data.iter().map(|r| r.0).max_by(fcmp).unwrap();
-    let min_y = data.iter().map(|r| r.1).min_by(fcmp).unwrap();
-    let max_y = data.iter().map(|r| r.1).max_by(fcmp).unwrap();
+    let min = values.clone().min_by(fcmp).unwrap();
+    let max = values.max_by(fcmp).unwrap();
     let zoom_out = 1.1;
-    let x_range = max_x - min_x;
-    let y_range = max_y - min_y;
-    let mid_x = min_x + x_range / 2.0;
-    let mid_y = min_y + y_range / 2.0;
-    let min_x = mid_x - (x_range * zoom_out) / 2.0;
-    let max_x = mid_x + (x_range * zoom_out) / 2.0;
-    let min_y = mid_y - (y_range * zoom_out) / 2.0;
-    let max_y = mid_y + (y_range * zoom_out) / 2.0;
-    create_dir_all(args.plot_out.clone()).unwrap();
+    let range = max - min;
+    let mid = min + range / 2.0;
+    mid - (range * zoom_out) / 2.0..mid + (range * zoom_out) / 2.0
+}
 
-    // let mut regressor = ScaledTranslatedEquation::new(|x: f64| x.abs().powf(-x.powi(2)));
-    // let mut regressor = ScaledTranslatedEquation {
-    //     x_0: 0.9409389715046765,
-    //     y_0: 0.0020235808788646808,
-    //     width: 0.01943965396363798,
-    //     height: 0.19151100798519274,
-    //     function: |x: f64| x.abs().powf(-x.powi(2))
-    // };
-    let mut regressor = ParametricScaledTranslatedEquation {
-        x_0: 0.0,
-        y_0: 1800.0,
-        width: 900.0,
-        height: 200.0,
-        parameters: [1.5, 4.0],
-        function: |x: f64, p: [f64; 2]| {
-            if x <= 0.0 {
-                0.0
-            } else {
-                x.powf(-(x.powf(p[1]) * p[0]))
-            }
+/// Add a fit plot to the animation if there is one, noting `progress` in its title, and otherwise
+/// save it to `path`
+fn show_fit(
+    figure: &impl Figure,
+    animation: Option<&Animation>,
+    style: &PlotStyle,
+    path: &Path,
+    progress: String,
+) -> Result<(), Box<dyn Error>> {
+    match animation {
+        Some(animation) => {
+            let mut style = style.clone();
+            style.title = Some(match &style.title {
+                Some(title) => format!("{title} ({progress})"),
+                None => progress,
+            });
+            animation.add_frame(figure, &style)
+        }
+        None => save(figure, path, style).map(|_| ()),
+    }
+}
+
+/// Parameters of the loaded model, checking they are those `names`, or else the initial values
+/// given on the command line, which may be none
+fn initial_values(
+    args: &Args,
+    saved: Option<&SavedModel>,
+    names: &[String],
+) -> Result<Vec<f64>, Box<dyn Error>> {
+    match saved {
+        Some(saved) => saved.parameter_values(names),
+        None => Ok(args.initial.clone()),
+    }
+}
+
+/// Start from the parameters of the loaded model or the initial values given on the command
+/// line, if any, instead of the regressor's defaults
+fn initialize<R>(
+    args: &Args,
+    model: &ModelSpec,
+    saved: Option<&SavedModel>,
+    regressor: &mut R,
+) -> Result<(), Box<dyn Error>>
+where
+    R: GradientDescent,
+    [(); R::PARAM_DIMENSION]:,
+{
+    let initial = initial_values(args, saved, &regressor.parameter_names())?;
+    match initial.len() {
+        0 => {}
+        n if n == R::PARAM_DIMENSION => regressor.set_parameters(array::from_fn(|i| initial[i])),
+        n => {
+            return Err(format!(
+                "{model:?} model takes {} initial values, but {n} were given",
+                R::PARAM_DIMENSION
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
+
+/// Regressor given by the command line
+fn model_spec(args: &Args) -> Result<ModelSpec, Box<dyn Error>> {
+    let function = args.function;
+    Ok(match args.model {
+        Model::Linear => ModelSpec::Linear,
+        Model::MultiLinear => ModelSpec::MultiLinear {
+            inputs: args.x_column.len(),
+        },
+        Model::Exponential => ModelSpec::Exponential,
+        Model::Polynomial => ModelSpec::Polynomial {
+            degree: args.degree,
         },
+        Model::ScaledTranslated => ModelSpec::ScaledTranslated { function },
+        Model::Parametric => ModelSpec::Parametric { function },
+        Model::ParametricScaledTranslated => ModelSpec::ParametricScaledTranslated { function },
+        Model::Expression => ModelSpec::Expression {
+            expression: args
+                .expression
+                .clone()
+                .ok_or("the expression model requires --expression")?,
+        },
+    })
+}
+
+/// What the fit plots draw of the data: each output against the first input, with its error bars,
+/// on axes framing it and the fitted curve. Each output is plotted in a panel of its own
+struct PlotData {
+    y_labels: Vec<String>,
+    observed: Vec<Vec<f64>>,
+    points: Vec<Vec<(f64, f64)>>,
+    sigmas: Option<Vec<Vec<f64>>>,
+    curve_xs: Vec<f64>,
+    bounds: Vec<(Range<f64>, Range<f64>)>,
+}
+
+impl PlotData {
+    fn new<const N: usize, const M: usize>(args: &Args, data: &[Record<N, M>]) -> Self {
+        let y_labels = match M {
+            1 => vec![args.y_label.clone()],
+            _ => (0..M).map(|k| format!("{}{k}", args.y_label)).collect(),
+        };
+        let observed = (0..M)
+            .map(|k| data.iter().map(|datum| datum.y[k]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // models of one input are plotted as a curve through the data, drawn over a grid of x
+        let points = observed
+            .iter()
+            .map(|ys| {
+                data.iter()
+                    .zip(ys)
+                    .map(|(datum, y)| (datum.x[0], *y))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let sigmas = (!args.sigma_column.is_empty()).then(|| {
+            (0..M)
+                .map(|k| {
+                    data.iter()
+                        .map(|datum| datum.weight[k].sqrt().recip())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        });
+        let curve_range = curve_range(args, data);
+        let curve_xs = grid(curve_range.clone(), args.samples, args.log_x);
+        let x_range = zoomed(data.iter().map(|datum| datum.x[0]));
+        let x_range = x_range.start.min(curve_range.start)..x_range.end.max(curve_range.end);
+        let bounds = (0..M)
+            .map(|k| {
+                // leave room for the error bars
+                let sigmas = sigmas.iter().flat_map(|sigmas| &sigmas[k]);
+                let y_range = zoomed(
+                    observed[k]
+                        .iter()
+                        .zip(sigmas.chain(iter::repeat(&0.0)))
+                        .flat_map(|(y, sigma)| [y - sigma, y + sigma]),
+                );
+                (x_range.clone(), y_range)
+            })
+            .collect::<Vec<_>>();
+        Self {
+            y_labels,
+            observed,
+            points,
+            sigmas,
+            curve_xs,
+            bounds,
+        }
+    }
+
+    /// Fit plot of each output of a model of one input, drawing `curve(k, x)`, the model's `k`th
+    /// output at `x`, through the data, and `bands(k)` around it if known
+    fn fit_panels(
+        &self,
+        equation: String,
+        curve: impl Fn(usize, f64) -> f64,
+        bands: impl Fn(usize) -> Option<Vec<Band>>,
+    ) -> Panels<FitPlot<'_>> {
+        let figures = (0..self.points.len())
+            .map(|k| FitPlot {
+                data: &self.points[k],
+                sigmas: self.sigmas.as_ref().map(|sigmas| &sigmas[k][..]),
+                curve: self.curve_xs.iter().map(|x| (*x, curve(k, *x))).collect(),
+                equation: equation.clone(),
+                bands: bands(k),
+                bounds: self.bounds[k].clone(),
+            })
+            .collect();
+        Panels {
+            figures,
+            y_labels: self.y_labels.clone(),
+        }
+    }
+}
+
+/// Save the fitted model to the file given by `--save-model`, if any
+fn save_model(
+    args: &Args,
+    model: &ModelSpec,
+    names: &[String],
+    values: &[f64],
+    covariance: Option<SavedCovariance>,
+    fit: FitMetadata,
+) -> Result<(), Box<dyn Error>> {
+    let Some(path) = &args.save_model else {
+        return Ok(());
     };
+    let saved = SavedModel {
+        model: model.clone(),
+        parameters: names
+            .iter()
+            .zip(values)
+            .map(|(name, value)| Parameter {
+                name: name.clone(),
+                value: *value,
+            })
+            .collect(),
+        covariance,
+        fit,
+    };
+    saved.save(path)
+}
 
-    for (i, should_print) in Observer::new_with(
+fn regress<R>(
+    args: &Args,
+    model: &ModelSpec,
+    regressor: R,
+    data: &[Record<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>],
+) -> Result<(), Box<dyn Error>>
+where
+    R: GradientDescent + Display,
+    [(); R::IN_DIMENSION]:,
+    [(); R::PARAM_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    if !(args.confidence > 0.0 && args.confidence < 1.0) {
+        return Err(format!("confidence {} is not between 0 and 1", args.confidence).into());
+    }
+    let loss = build_loss(args)?;
+    let options = FitOptions {
+        solver: args.solver,
+        loss,
+        optimizer: build_optimizer::<{ R::PARAM_DIMENSION }>(args),
+        damping: args.damping,
+        output_weights: args.output_weights.clone(),
+        finite_differences: args.finite_differences,
+        epsilon: args.epsilon,
+        finish_threshold: args.finish_threshold,
+        max_iterations: None,
+    };
+    if options.solver == Solver::Direct && !R::LINEAR {
+        return Err(format!(
+            "the direct solver requires a model linear in its parameters, which {model:?} is not"
+        )
+        .into());
+    }
+    let output_weights = options.output_weights(R::OUT_DIMENSION)?;
+    let weighted = weighted(args);
+    let style = plot_style(args);
+    let plot_data = PlotData::new(args, data);
+    let outputs = 0..R::OUT_DIMENSION;
+    let animation = args
+        .gif
+        .then(|| Animation::new(&args.plot_out.join("fit"), &style, args.frame_delay))
+        .transpose()?;
+    let mut observer = Observer::new_with(
         Duration::from_secs_f64(args.print_interval),
         progress_observer::Options {
             checkpoint_size: 100,
             ..Default::default()
         },
-    )
-    .enumerate()
-    {
-        let base_error: f64 = data
+    );
+    let result = fit_with(regressor, data, options, |iteration| {
+        let should_print = observer.next().unwrap_or(false);
+        if !(should_print || iteration.finished) {
+            return Ok(());
+        }
+        let Iteration {
+            iteration: i,
+            error: base_error,
+            magnitude,
+            regressor,
+            ..
+        } = *iteration;
+        println!("i: {i}, r: {regressor}, mag: {magnitude} err: {base_error}");
+
+        let path = args.plot_out.join(i.to_string());
+        let progress = format!("iteration {i}, error {base_error}");
+        match R::IN_DIMENSION {
+            1 => {
+                // draw plot for iteration result, shading the prediction band, unless the data
+                // were weighted, and within it the confidence band of the mean curve
+                let (residuals, jacobian) = residuals_and_jacobian(
+                    regressor,
+                    data,
+                    &output_weights,
+                    args.finite_differences,
+                    args.epsilon,
+                );
+                let covariance = Covariance::new(&jacobian, &residuals);
+                let figure = plot_data.fit_panels(
+                    regressor.to_string(),
+                    |k, x| regressor.predict(None, &array::from_fn(|_| x))[k],
+                    |k| {
+                        covariance.as_ref().map(|covariance| {
+                            plot_data
+                                .curve_xs
+                                .iter()
+                                .map(|x| {
+                                    let input = array::from_fn(|_| *x);
+                                    let gradient = model_jacobian(
+                                        regressor,
+                                        &input,
+                                        args.finite_differences,
+                                        args.epsilon,
+                                    )[k];
+                                    let (mean_error, prediction_error) =
+                                        covariance.prediction_errors(gradient, output_weights[k]);
+                                    Band {
+                                        x: *x,
+                                        y: regressor.predict(None, &input)[k],
+                                        confidence: covariance.margin(mean_error, args.confidence),
+                                        prediction: (!weighted).then(|| {
+                                            covariance.margin(prediction_error, args.confidence)
+                                        }),
+                                    }
+                                })
+                                .collect()
+                        })
+                    },
+                );
+                show_fit(&figure, animation.as_ref(), &style, &path, progress)?;
+            }
+            2 => {
+                let inputs = data
+                    .iter()
+                    .map(|datum| [datum.x[0], datum.x[1]])
+                    .collect::<Vec<_>>();
+                let bounds = (
+                    zoomed(inputs.iter().map(|[x, _]| *x)),
+                    zoomed(inputs.iter().map(|[_, y]| *y)),
+                );
+                let centers = |range: &Range<f64>| {
+                    let width = (range.end - range.start) / args.surface_cells as f64;
+                    (0..args.surface_cells)
+                        .map(move |i| range.start + (i as f64 + 0.5) * width)
+                        .collect::<Vec<_>>()
+                };
+                let ys = centers(&bounds.1);
+                let surfaces = centers(&bounds.0)
+                    .into_iter()
+                    .map(|x| {
+                        ys.iter()
+                            .map(|y| regressor.predict(None, &array::from_fn(|i| [x, *y][i])))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+                let data = plot_data
+                    .observed
+                    .iter()
+                    .map(|ys| inputs.iter().copied().zip(ys.iter().copied()).collect())
+                    .collect::<Vec<Vec<_>>>();
+                let figures = outputs
+                    .clone()
+                    .map(|k| SurfacePlot {
+                        data: &data[k],
+                        surface: surfaces
+                            .iter()
+                            .map(|row| row.iter().map(|output| output[k]).collect())
+                            .collect(),
+                        equation: regressor.to_string(),
+                        bounds: bounds.clone(),
+                    })
+                    .collect();
+                let figure = Panels {
+                    figures,
+                    y_labels: plot_data.y_labels.clone(),
+                };
+                show_fit(&figure, animation.as_ref(), &style, &path, progress)?;
+            }
+            _ => {
+                let predicted = data
+                    .iter()
+                    .map(|datum| regressor.predict(None, &datum.x))
+                    .collect::<Vec<_>>();
+                let figures = outputs
+                    .clone()
+                    .map(|k| ParityPlot {
+                        observed: &plot_data.observed[k],
+                        predicted: predicted.iter().map(|output| output[k]).collect(),
+                        equation: regressor.to_string(),
+                    })
+                    .collect();
+                let figure = Panels {
+                    figures,
+                    y_labels: plot_data.y_labels.clone(),
+                };
+                show_fit(&figure, animation.as_ref(), &style, &path, progress)?;
+            }
+        }
+
+        if iteration.finished {
+            println!("Done after {i} iterations");
+        }
+        Ok(())
+    })?;
+    let regressor = result.regressor;
+    let history = result.history;
+    let names = regressor.parameter_names();
+    println!("{regressor}");
+    save(
+        &Convergence {
+            history: &history,
+            parameters: args.plot_parameters,
+            names: &names,
+        },
+        &args.plot_out.join("convergence"),
+        &style,
+    )?;
+    if args.history_csv {
+        history.write_csv(&args.plot_out.join("history.csv"), &names)?;
+    }
+
+    let predicted = data
+        .iter()
+        .map(|datum| regressor.predict(None, &datum.x))
+        .collect::<Vec<_>>();
+    let (residuals, _) = residuals_and_jacobian(
+        &regressor,
+        data,
+        &output_weights,
+        args.finite_differences,
+        args.epsilon,
+    );
+    print_loss_caveat(loss);
+    let mut diagnostics = Vec::new();
+    for k in outputs {
+        let weights = data.iter().map(|datum| datum.weight[k]).collect::<Vec<_>>();
+        let fitted = predicted.iter().map(|output| output[k]).collect::<Vec<_>>();
+        if R::OUT_DIMENSION > 1 {
+            println!("{}:", plot_data.y_labels[k]);
+        }
+        println!(
+            "{}",
+            FitReport::new(
+                &plot_data.observed[k],
+                &fitted,
+                &weights,
+                R::PARAM_DIMENSION
+            )
+        );
+        let residuals = residuals
             .iter()
-            .map(|datum| {
-                let delta = datum.1 - regressor.predict(None, &[datum.0])[0];
-                delta * delta
-            })
-            .sum();
-        let gradients = array::from_fn(|nudge| {
-            let gradient_error: f64 = data
+            .skip(k)
+            .step_by(R::OUT_DIMENSION)
+            .copied()
+            .collect::<Vec<_>>();
+        diagnostics.push((fitted, residuals));
+    }
+    let xs = data.iter().map(|datum| datum.x[0]).collect::<Vec<_>>();
+    // the residuals are scaled by the square roots of their weights, which makes them
+    // residuals in units of their sigmas if those are given
+    let label = if !args.sigma_column.is_empty() {
+        "normalized residual"
+    } else if weighted || output_weights.iter().any(|weight| *weight != 1.0) {
+        "weighted residual"
+    } else {
+        "residual"
+    };
+    save(
+        &Panels {
+            figures: diagnostics
                 .iter()
-                .map(|datum| {
-                    let delta =
-                        datum.1 - regressor.predict(Some((nudge, args.epsilon)), &[datum.0])[0];
-                    delta * delta
+                .map(|(fitted, residuals)| ResidualDiagnostics {
+                    x: &xs,
+                    fitted,
+                    residuals,
+                    label,
                 })
-                .sum();
-            (base_error - gradient_error) / args.epsilon
-        });
-        let magnitude = gradients.magnitude();
-        let should_finish = magnitude.abs() <= args.finish_threshold;
-        regressor.descend(gradients.map(|x| x * args.temperature));
-        if should_print || should_finish {
-            println!("i: {i}, r: {regressor}, mag: {magnitude} err: {base_error}");
-
-            // draw plot for iteration result
-            let img_name = format!("{}/{i}.png", args.plot_out.to_string_lossy());
-            let root = BitMapBackend::new(&img_name, (1920, 1080)).into_drawing_area();
-            root.fill(&WHITE)?;
-            let mut plot = ChartBuilder::on(&root)
-                .margin(5)
-                .x_label_area_size(40)
-                .y_label_area_size(50)
-                .build_cartesian_2d(min_x..max_x, min_y..max_y)
-                .unwrap();
-            plot.configure_mesh().draw().unwrap();
-            plot.draw_series(
-                data.iter()
-                    .map(|Record(x, y)| Circle::new((*x, *y), 1, GREEN.filled())),
-            )
-            .unwrap();
-            plot.draw_series(LineSeries::new(
-                data.iter()
-                    .map(|Record(x, _)| (*x, regressor.predict(None, &[*x])[0])),
-                RED.stroke_width(2),
-            ))
-            .unwrap();
-            root.present().unwrap();
-
-            if should_finish {
-                println!("Done after {i} iterations");
-                break;
+                .collect(),
+            y_labels: plot_data.y_labels,
+        },
+        &args.plot_out.join("residuals"),
+        &style,
+    )?;
+    match &result.covariance {
+        Some(covariance) => {
+            println!(
+                "parameters, with {}% confidence intervals:",
+                args.confidence * 100.0
+            );
+            let estimates = covariance.estimates(regressor.parameters(), args.confidence);
+            for (name, estimate) in names.iter().zip(&estimates) {
+                println!("  {name}: {estimate}");
+            }
+        }
+        None => println!(
+            "parameters are not identifiable from the data, or there are too few data to \
+             estimate their uncertainties"
+        ),
+    }
+
+    save_model(
+        args,
+        model,
+        &names,
+        &regressor.parameters(),
+        result
+            .covariance
+            .as_ref()
+            .map(|covariance| SavedCovariance {
+                observation_weights: (!weighted).then(|| output_weights.clone()),
+                ..SavedCovariance::from(covariance)
+            }),
+        FitMetadata {
+            data_file: args.data_file().to_path_buf(),
+            loss,
+            iterations: result.iterations,
+            error: Some(result.error),
+            converged: result.converged,
+            timestamp: FitMetadata::now(),
+        },
+    )?;
+    Ok(())
+}
+
+/// Warn that the report and the uncertainties of the parameters, which assume least squares,
+/// don't describe fits of other losses exactly
+fn print_loss_caveat(loss: Loss) {
+    if !matches!(loss, Loss::Squared) {
+        println!("the fit minimized {loss:?} loss, but the statistics below assume squared error");
+    }
+}
+
+/// Evaluate `$body` with `$p` bound to a constant equal to `$n`, or `$fallback` with `$other`
+/// bound to `$n` if there is no such constant
+macro_rules! with_parameters {
+    ($n:expr, $p:ident => $body:expr) => {
+        with_parameters!($n, $p => $body, n => {
+            Err(format!("models with {n} parameters are not supported").into())
+        })
+    };
+    ($n:expr, $p:ident => $body:expr, $other:ident => $fallback:expr) => {
+        with_parameters!(@ $n, $p => $body, $other => $fallback; 0 1 2 3 4 5 6 7 8)
+    };
+    (@ $n:expr, $p:ident => $body:expr, $other:ident => $fallback:expr; $($i:literal)*) => {
+        match $n {
+            $($i => {
+                const $p: usize = $i;
+                $body
+            })*
+            $other => $fallback,
+        }
+    };
+}
+
+/// Evaluate `$body` with `$outputs` bound to a constant equal to `$n`
+macro_rules! with_outputs {
+    ($n:expr, $outputs:ident => $body:expr) => {
+        with_outputs!(@ $n, $outputs => $body; 1 2 3 4)
+    };
+    (@ $n:expr, $outputs:ident => $body:expr; $($i:literal)*) => {
+        match $n {
+            $($i => {
+                const $outputs: usize = $i;
+                $body
+            })*
+            n => Err(format!("models with {n} outputs are not supported").into()),
+        }
+    };
+}
+
+/// Evaluate `$body` with `$inputs` bound to a constant equal to `$n`
+macro_rules! with_inputs {
+    ($n:expr, $inputs:ident => $body:expr) => {
+        with_inputs!(@ $n, $inputs => $body; 1 2 3 4 5 6 7 8)
+    };
+    (@ $n:expr, $inputs:ident => $body:expr; $($i:literal)*) => {
+        match $n {
+            $($i => {
+                const $inputs: usize = $i;
+                $body
+            })*
+            n => Err(format!("models with {n} inputs are not supported").into()),
+        }
+    };
+}
+
+/// Evaluate `$body` with `$terms` bound to a constant equal to `$degree + 1`, or `$fallback`
+/// with `$other` bound to `$degree` if there is no such constant
+macro_rules! with_terms {
+    ($degree:expr, $terms:ident => $body:expr, $other:ident => $fallback:expr) => {
+        with_terms!(@ $degree, $terms => $body, $other => $fallback; 0 1 2 3 4 5 6 7 8 9 10)
+    };
+    (@ $degree:expr, $terms:ident => $body:expr, $other:ident => $fallback:expr; $($n:literal)*) => {
+        match $degree {
+            $($n => {
+                const $terms: usize = $n + 1;
+                $body
+            })*
+            $other => $fallback,
+        }
+    };
+}
+
+/// Predict each output of the regressor at the inputs of the CSV file and write them as CSV,
+/// with the interval of each if the `covariance` of the parameters is known: where a new
+/// observation falls, or for models fit to weighted data, of which new observations have no
+/// known weight, where the mean output falls
+fn predict<const INPUTS: usize>(
+    args: &PredictArgs,
+    regressor: &dyn Regressor,
+    covariance: Option<&SavedCovariance>,
+) -> Result<(), Box<dyn Error>> {
+    if !(args.confidence > 0.0 && args.confidence < 1.0) {
+        return Err(format!("confidence {} is not between 0 and 1", args.confidence).into());
+    }
+    let options = CsvOptions {
+        x: if args.x_column.is_empty() {
+            (0..INPUTS).map(Column::Index).collect()
+        } else {
+            args.x_column.clone()
+        },
+        delimiter: args.delimiter,
+        quote: args.quote,
+        comment: args.comment,
+        headers: !args.no_headers,
+        ..CsvOptions::default()
+    };
+    let data = load::<INPUTS, 0>(&args.data_file, &options)?;
+    let (parameters, outputs) = (regressor.param_count(), regressor.output_count());
+    if let Some(saved) = covariance {
+        if saved.matrix.len() != parameters
+            || saved.matrix.iter().any(|row| row.len() != parameters)
+        {
+            return Err(
+                format!("the saved covariance is not a {parameters}x{parameters} matrix").into(),
+            );
+        }
+        if let Some(weights) = saved.observation_weights.as_ref() {
+            if weights.len() != outputs {
+                return Err(format!(
+                    "the saved model has observation weights for other than {outputs} outputs"
+                )
+                .into());
+            }
+        }
+    }
+    let weights = covariance.and_then(|saved| saved.observation_weights.as_deref());
+    let covariance = covariance.map(DynamicCovariance::from);
+
+    let output: Box<dyn Write> = if args.output == Path::new("-") {
+        Box::new(io::stdout().lock())
+    } else {
+        let file = File::create(&args.output)
+            .map_err(|err| format!("cannot create {}: {err}", args.output.display()))?;
+        Box::new(file)
+    };
+    let mut writer = csv::Writer::from_writer(output);
+    let name = |name: &str, i: usize, count: usize| match count {
+        1 => name.to_string(),
+        _ => format!("{name}{i}"),
+    };
+    let mut header = (0..INPUTS)
+        .map(|i| name("x", i, INPUTS))
+        .collect::<Vec<_>>();
+    for k in 0..outputs {
+        let y = name("y", k, outputs);
+        if covariance.is_some() {
+            let prefix = match weights {
+                Some(_) => y.clone(),
+                None => format!("{y}_mean"),
+            };
+            header.extend([format!("{prefix}_lower"), y, format!("{prefix}_upper")]);
+        } else {
+            header.push(y);
+        }
+    }
+    writer.write_record(&header)?;
+    for record in &data {
+        let predicted = regressor.predict(None, &record.x);
+        let mut row = record.x.to_vec();
+        match &covariance {
+            Some(covariance) => {
+                let jacobian = regressor.jacobian(&record.x, args.epsilon);
+                for (k, (y, gradient)) in predicted.into_iter().zip(jacobian).enumerate() {
+                    let weight = weights.map_or(1.0, |weights| weights[k]);
+                    let (mean_error, prediction_error) =
+                        covariance.prediction_errors(&gradient, weight);
+                    let error = match weights {
+                        Some(_) => prediction_error,
+                        None => mean_error,
+                    };
+                    let margin = covariance.margin(error, args.confidence);
+                    row.extend([y - margin, y, y + margin]);
+                }
             }
+            None => row.extend(predicted),
         }
+        writer.write_record(row.iter().map(f64::to_string))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Start the regressor from the given parameters, then predict with it if asked to, and
+/// otherwise load the data for its inputs and outputs and fit it
+fn fit<R>(
+    args: &Args,
+    model: &ModelSpec,
+    saved: Option<&SavedModel>,
+    mut regressor: R,
+) -> Result<(), Box<dyn Error>>
+where
+    R: GradientDescent + Display,
+    [(); R::IN_DIMENSION]:,
+    [(); R::PARAM_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    initialize(args, model, saved, &mut regressor)?;
+    if let Some(Command::Predict(predict_args)) = &args.command {
+        let covariance = saved.and_then(|saved| saved.covariance.as_ref());
+        return predict::<{ R::IN_DIMENSION }>(predict_args, &Fixed(regressor), covariance);
     }
+    create_dir_all(args.plot_out.clone()).unwrap();
+    let data =
+        load::<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>(args.data_file(), &csv_options(args))?;
+    regress(args, model, regressor, &data)
+}
+
+/// [`fit`] for models whose size is only known at runtime, which cover the polynomials of higher
+/// degree and the expressions with more parameters than there are fixed size regressors for.
+/// These are fit by [`dynamic::fit`], drawing only the final fit
+fn fit_runtime<const OUTPUTS: usize>(
+    args: &Args,
+    model: &ModelSpec,
+    saved: Option<&SavedModel>,
+    mut regressor: impl Regressor,
+) -> Result<(), Box<dyn Error>> {
+    let initial = initial_values(args, saved, &regressor.parameter_names())?;
+    if !initial.is_empty() {
+        regressor.set_parameters(&initial)?;
+    }
+    if let Some(Command::Predict(predict_args)) = &args.command {
+        let covariance = saved.and_then(|saved| saved.covariance.as_ref());
+        return predict::<1>(predict_args, &regressor, covariance);
+    }
+    if !(args.confidence > 0.0 && args.confidence < 1.0) {
+        return Err(format!("confidence {} is not between 0 and 1", args.confidence).into());
+    }
+    let loss = build_loss(args)?;
+    match args.solver {
+        Solver::Auto => {}
+        Solver::LevenbergMarquardt if matches!(loss, Loss::Squared) => {}
+        Solver::LevenbergMarquardt => {
+            return Err(format!(
+                "the {:?} solver only minimizes squared error, not {loss:?}",
+                args.solver
+            )
+            .into())
+        }
+        solver => {
+            return Err(format!(
+                "models of {} parameters are fit by Levenberg-Marquardt for squared error and by \
+                 gradient descent otherwise, not the {solver:?} solver",
+                regressor.param_count()
+            )
+            .into())
+        }
+    }
+    if !matches!(args.optimizer, OptimizerKind::Sgd) {
+        return Err(format!(
+            "models of {} parameters only support the default optimizer",
+            regressor.param_count()
+        )
+        .into());
+    }
+    create_dir_all(&args.plot_out)?;
+    let data = load::<1, OUTPUTS>(args.data_file(), &csv_options(args))?;
+    let samples = data.iter().copied().map(Sample::from).collect::<Vec<_>>();
+    let options = dynamic::FitOptions {
+        loss,
+        learning_rate: args.temperature,
+        damping: args.damping,
+        output_weights: args.output_weights.clone(),
+        epsilon: args.epsilon,
+        finish_threshold: args.finish_threshold,
+        max_iterations: None,
+    };
+    let result = dynamic::fit(&mut regressor, &samples, &options)?;
+    println!("Done after {} iterations", result.iterations);
     println!("{regressor}");
 
+    // shade the prediction band, unless the data were weighted, and within it the confidence
+    // band of the mean curve
+    let weighted = weighted(args);
+    let plot_data = PlotData::new(args, &data);
+    let figure = plot_data.fit_panels(
+        regressor.to_string(),
+        |k, x| regressor.predict(None, &[x])[k],
+        |k| {
+            result.covariance.as_ref().map(|covariance| {
+                plot_data
+                    .curve_xs
+                    .iter()
+                    .map(|x| {
+                        let gradient = &regressor.jacobian(&[*x], args.epsilon)[k];
+                        let (mean_error, prediction_error) =
+                            covariance.prediction_errors(gradient, result.output_weights[k]);
+                        Band {
+                            x: *x,
+                            y: regressor.predict(None, &[*x])[k],
+                            confidence: covariance.margin(mean_error, args.confidence),
+                            prediction: (!weighted)
+                                .then(|| covariance.margin(prediction_error, args.confidence)),
+                        }
+                    })
+                    .collect()
+            })
+        },
+    );
+    let path = args.plot_out.join((result.iterations - 1).to_string());
+    save(&figure, &path, &plot_style(args))?;
+    print_loss_caveat(loss);
+    for (k, observed) in plot_data.observed.iter().enumerate() {
+        let weights = data.iter().map(|datum| datum.weight[k]).collect::<Vec<_>>();
+        let fitted = samples
+            .iter()
+            .map(|sample| regressor.predict(None, &sample.x)[k])
+            .collect::<Vec<_>>();
+        if OUTPUTS > 1 {
+            println!("{}:", plot_data.y_labels[k]);
+        }
+        println!(
+            "{}",
+            FitReport::new(observed, &fitted, &weights, regressor.param_count())
+        );
+    }
+    let names = regressor.parameter_names();
+    match &result.covariance {
+        Some(covariance) => {
+            println!(
+                "parameters, with {}% confidence intervals:",
+                args.confidence * 100.0
+            );
+            let estimates = covariance.estimates(&regressor.parameters(), args.confidence);
+            for (name, estimate) in names.iter().zip(&estimates) {
+                println!("  {name}: {estimate}");
+            }
+        }
+        None => println!(
+            "parameters are not identifiable from the data, or there are too few data to \
+             estimate their uncertainties"
+        ),
+    }
+
+    save_model(
+        args,
+        model,
+        &names,
+        &regressor.parameters(),
+        result
+            .covariance
+            .as_ref()
+            .map(|covariance| SavedCovariance {
+                observation_weights: (!weighted).then(|| result.output_weights.clone()),
+                ..SavedCovariance::from(covariance)
+            }),
+        FitMetadata {
+            data_file: args.data_file().to_path_buf(),
+            loss,
+            iterations: result.iterations,
+            error: Some(result.error),
+            converged: result.converged,
+            timestamp: FitMetadata::now(),
+        },
+    )?;
     Ok(())
 }
 
-fn main() {
-    if let Err(err) = run(Args::parse()) {
-        eprintln!("{err}");
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let saved = match &args.command {
+        Some(Command::Predict(predict_args)) => Some(SavedModel::load(&predict_args.model)?),
+        None => args
+            .load_model
+            .as_deref()
+            .map(SavedModel::load)
+            .transpose()?,
+    };
+    let model = match &saved {
+        Some(saved) => saved.model.clone(),
+        None => model_spec(&args)?,
+    };
+    let saved = saved.as_ref();
+    match &model {
+        ModelSpec::Linear => fit(&args, &model, saved, Linear::default()),
+        &ModelSpec::MultiLinear { inputs } => with_inputs!(inputs, INPUTS => {
+            fit(&args, &model, saved, MultiLinear::<INPUTS>::default())
+        }),
+        ModelSpec::Exponential => fit(&args, &model, saved, Exponential::default()),
+        &ModelSpec::Polynomial { degree } => with_terms!(degree, TERMS => {
+            fit(&args, &model, saved, Polynomial::<TERMS>::default())
+        }, degree => {
+            let regressor = dynamic::Polynomial {
+                terms: vec![1.0; degree + 1],
+            };
+            fit_runtime::<1>(&args, &model, saved, regressor)
+        }),
+        &ModelSpec::ScaledTranslated { function } => {
+            if function.parameters() != 0 {
+                return Err(format!(
+                    "{function:?} takes parameters; use the parametric-scaled-translated model"
+                )
+                .into());
+            }
+            let f = function.parametric::<Dual<4>, 0>();
+            let regressor = ScaledTranslatedEquation::new(move |x| f(x, []));
+            fit(&args, &model, saved, regressor)
+        }
+        &ModelSpec::Parametric { function } => with_parameters!(function.parameters(), P => {
+            let mut regressor = ParametricEquation::new(function.parametric::<Dual<P>, P>());
+            regressor.parameters = [1.0; P];
+            fit(&args, &model, saved, regressor)
+        }),
+        &ModelSpec::ParametricScaledTranslated { function } => {
+            with_parameters!(function.parameters(), P => {
+                let mut regressor = ParametricScaledTranslatedEquation::new(
+                    function.parametric::<Dual<{ 4 + P }>, P>(),
+                );
+                regressor.parameters = [1.0; P];
+                fit(&args, &model, saved, regressor)
+            })
+        }
+        ModelSpec::Expression { expression } => {
+            let expressions = Expression::parse_system(expression)
+                .map_err(|err| format!("invalid expression `{expression}` {err}"))?;
+            with_outputs!(expressions.len(), OUTPUTS => {
+                with_parameters!(expressions[0].parameters().len(), P => {
+                    let regressor = Formula::<P, OUTPUTS> {
+                        parameters: [1.0; P],
+                        expressions: expressions.clone().try_into().unwrap(),
+                    };
+                    fit(&args, &model, saved, regressor)
+                }, parameters => {
+                    let regressor = dynamic::Formula {
+                        parameters: vec![1.0; parameters],
+                        expressions: expressions.clone(),
+                    };
+                    fit_runtime::<OUTPUTS>(&args, &model, saved, regressor)
+                })
+            })
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{env, fs};
+
     use clap::Parser;
+    use regression::{
+        regressors::{Linear, Polynomial},
+        saved::{ModelSpec, SavedModel},
+    };
 
-    use crate::{run, Args};
+    use crate::{initialize, model_spec, run, Args};
 
     #[test]
     fn test() {
@@ -252,4 +1465,188 @@ mod tests {
             eprintln!("{err}");
         }
     }
+
+    #[test]
+    fn selects_model_and_initial_values() {
+        let args = Args::parse_from([
+            "_",
+            "data.csv",
+            "-m",
+            "polynomial",
+            "-d",
+            "3",
+            "-i",
+            "1,2,-3,4",
+        ]);
+        assert_eq!(
+            model_spec(&args).unwrap(),
+            ModelSpec::Polynomial { degree: 3 }
+        );
+        let mut regressor = Polynomial::<4>::default();
+        let model = model_spec(&args).unwrap();
+        initialize(&args, &model, None, &mut regressor).unwrap();
+        assert_eq!(regressor.terms, [1.0, 2.0, -3.0, 4.0]);
+
+        // keeps the defaults without initial values, and rejects the wrong number of them
+        let args = Args::parse_from(["_", "data.csv"]);
+        let mut regressor = Linear::default();
+        initialize(&args, &ModelSpec::Linear, None, &mut regressor).unwrap();
+        assert_eq!((regressor.slope, regressor.y_intercept), (1.0, 0.0));
+        let args = Args::parse_from(["_", "data.csv", "-i", "1,2,3"]);
+        let error = initialize(&args, &ModelSpec::Linear, None, &mut regressor).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Linear model takes 2 initial values, but 3 were given"
+        );
+
+        let args = Args::parse_from(["_", "data.csv", "-m", "expression"]);
+        assert!(model_spec(&args).is_err());
+    }
+
+    #[test]
+    fn fits_models_of_runtime_size() {
+        // more terms than there are fixed size polynomials for
+        let terms = [
+            1.0, -0.5, 0.25, 0.1, -0.02, 3e-3, -2e-4, 1e-5, -3e-7, 4e-9, 1e-10, -2e-11,
+        ];
+        let dir = env::temp_dir().join(format!("runtime-size-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut csv = "x,y\n".to_string();
+        for i in 0..40 {
+            let x = i as f64 / 4.0 - 5.0;
+            let y = terms.iter().rev().fold(0.0, |sum, term| sum * x + term);
+            csv.push_str(&format!("{x},{y}\n"));
+        }
+        let (data, model) = (dir.join("data.csv"), dir.join("model.json"));
+        fs::write(&data, csv).unwrap();
+        let path = |path: &std::path::Path| path.to_str().unwrap().to_string();
+        let args = Args::parse_from([
+            "_".into(),
+            path(&data),
+            "-m".into(),
+            "polynomial".into(),
+            "-d".into(),
+            "11".into(),
+            "--solver".into(),
+            "levenberg-marquardt".into(),
+            "--save-model".into(),
+            path(&model),
+            "-o".into(),
+            path(&dir.join("plots")),
+            "--width".into(),
+            "320".into(),
+            "--height".into(),
+            "240".into(),
+        ]);
+        run(args).unwrap();
+        let saved = SavedModel::load(&model).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(saved.model, ModelSpec::Polynomial { degree: 11 });
+        // with the uncertainties of its parameters
+        let covariance = saved.covariance.unwrap();
+        assert_eq!(covariance.matrix.len(), terms.len());
+        assert_eq!(covariance.degrees_of_freedom, 40.0 - terms.len() as f64);
+        for (parameter, expected) in saved.parameters.iter().zip(terms) {
+            assert!((parameter.value - expected).abs() < 1e-6, "{parameter:?}");
+        }
+    }
+
+    #[test]
+    fn predicts_with_saved_model() {
+        let dir = env::temp_dir().join(format!("predict-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let data = (0..10)
+            .map(|i| format!("{i},{},0.1\n", 2.0 * i as f64 + 1.0 + [0.1, -0.1][i % 2]))
+            .collect::<String>();
+        let (data_file, model, inputs, output) = (
+            dir.join("data.csv"),
+            dir.join("model.json"),
+            dir.join("inputs.csv"),
+            dir.join("predictions.csv"),
+        );
+        fs::write(&data_file, format!("x,y,s\n{data}")).unwrap();
+        fs::write(&inputs, "x\n0\n2.5\n").unwrap();
+        let path = |path: &std::path::Path| path.to_str().unwrap().to_string();
+        run(Args::parse_from([
+            "_".into(),
+            path(&data_file),
+            "--save-model".into(),
+            path(&model),
+            "-o".into(),
+            path(&dir.join("plots")),
+            "--width".into(),
+            "320".into(),
+            "--height".into(),
+            "240".into(),
+        ]))
+        .unwrap();
+        run(Args::parse_from([
+            "_".into(),
+            "predict".into(),
+            path(&model),
+            path(&inputs),
+            "-o".into(),
+            path(&output),
+        ]))
+        .unwrap();
+        let saved = SavedModel::load(&model).unwrap();
+        let predictions = fs::read_to_string(&output).unwrap();
+
+        // data weighted by their errors leave only the interval of the mean output, as narrow as
+        // those errors rather than as the weighted residuals
+        run(Args::parse_from([
+            "_".into(),
+            path(&data_file),
+            "--sigma-column".into(),
+            "s".into(),
+            "--save-model".into(),
+            path(&model),
+            "-o".into(),
+            path(&dir.join("plots")),
+            "--width".into(),
+            "320".into(),
+            "--height".into(),
+            "240".into(),
+        ]))
+        .unwrap();
+        run(Args::parse_from([
+            "_".into(),
+            "predict".into(),
+            path(&model),
+            path(&inputs),
+            "-o".into(),
+            path(&output),
+        ]))
+        .unwrap();
+        let weighted = fs::read_to_string(&output).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let mut lines = weighted.lines();
+        assert_eq!(lines.next(), Some("x,y_mean_lower,y,y_mean_upper"));
+        for line in lines {
+            let row = line.split(',').map(|field| field.parse::<f64>().unwrap());
+            let [_, lower, y, _] = row.collect::<Vec<_>>()[..] else {
+                panic!("unexpected row {line}");
+            };
+            assert!(y - lower > 0.0 && y - lower < 0.2, "{line}");
+        }
+
+        let [slope, y_intercept] = [0, 1].map(|i| saved.parameters[i].value);
+        assert_eq!(predictions.lines().count(), 3);
+        let mut lines = predictions.lines();
+        assert_eq!(lines.next(), Some("x,y_lower,y,y_upper"));
+        for (line, x) in lines.zip([0.0, 2.5]) {
+            let row = line
+                .split(',')
+                .map(|field| field.parse::<f64>().unwrap())
+                .collect::<Vec<_>>();
+            let [input, lower, y, upper] = row[..] else {
+                panic!("unexpected row {line}");
+            };
+            assert_eq!(input, x);
+            assert!((y - (slope * x + y_intercept)).abs() < 1e-12);
+            // the prediction interval is centered on the prediction, and wider than the noise
+            assert!(((y - lower) - (upper - y)).abs() < 1e-9);
+            assert!(y - lower > 0.1 && y - lower < 1.0);
+        }
+    }
 }
@@ -0,0 +1,183 @@
+use std::array;
+
+/// Update rule that turns the gradient of the error into adjustments to the parameters.
+/// Gradients point downhill, so adjustments are applied by adding them to the parameters
+pub trait Optimizer<const N: usize> {
+    fn step(&mut self, gradients: [f64; N]) -> [f64; N];
+}
+
+/// Plain gradient descent: each adjustment is the gradient scaled by the learning rate
+pub struct Sgd {
+    pub learning_rate: f64,
+}
+
+impl<const N: usize> Optimizer<N> for Sgd {
+    fn step(&mut self, gradients: [f64; N]) -> [f64; N] {
+        gradients.map(|gradient| gradient * self.learning_rate)
+    }
+}
+
+/// Gradient descent with a velocity that accumulates past gradients, optionally looking ahead
+/// along the velocity as in Nesterov's accelerated gradient
+pub struct Momentum<const N: usize> {
+    pub learning_rate: f64,
+    pub momentum: f64,
+    pub nesterov: bool,
+    velocity: [f64; N],
+}
+
+impl<const N: usize> Momentum<N> {
+    pub fn new(learning_rate: f64, momentum: f64, nesterov: bool) -> Self {
+        Self {
+            learning_rate,
+            momentum,
+            nesterov,
+            velocity: [0.0; N],
+        }
+    }
+}
+
+impl<const N: usize> Optimizer<N> for Momentum<N> {
+    fn step(&mut self, gradients: [f64; N]) -> [f64; N] {
+        for (velocity, gradient) in self.velocity.iter_mut().zip(gradients) {
+            *velocity = self.momentum * *velocity + self.learning_rate * gradient;
+        }
+        if self.nesterov {
+            array::from_fn(|i| self.momentum * self.velocity[i] + self.learning_rate * gradients[i])
+        } else {
+            self.velocity
+        }
+    }
+}
+
+/// Per-parameter learning rates that shrink with the sum of all past squared gradients
+pub struct Adagrad<const N: usize> {
+    pub learning_rate: f64,
+    pub stabilizer: f64,
+    squared_sum: [f64; N],
+}
+
+impl<const N: usize> Adagrad<N> {
+    pub fn new(learning_rate: f64, stabilizer: f64) -> Self {
+        Self {
+            learning_rate,
+            stabilizer,
+            squared_sum: [0.0; N],
+        }
+    }
+}
+
+impl<const N: usize> Optimizer<N> for Adagrad<N> {
+    fn step(&mut self, gradients: [f64; N]) -> [f64; N] {
+        array::from_fn(|i| {
+            self.squared_sum[i] += gradients[i] * gradients[i];
+            self.learning_rate * gradients[i] / (self.squared_sum[i].sqrt() + self.stabilizer)
+        })
+    }
+}
+
+/// Per-parameter learning rates scaled by a decaying average of recent squared gradients
+pub struct RmsProp<const N: usize> {
+    pub learning_rate: f64,
+    pub decay: f64,
+    pub stabilizer: f64,
+    squared_average: [f64; N],
+}
+
+impl<const N: usize> RmsProp<N> {
+    pub fn new(learning_rate: f64, decay: f64, stabilizer: f64) -> Self {
+        Self {
+            learning_rate,
+            decay,
+            stabilizer,
+            squared_average: [0.0; N],
+        }
+    }
+}
+
+impl<const N: usize> Optimizer<N> for RmsProp<N> {
+    fn step(&mut self, gradients: [f64; N]) -> [f64; N] {
+        array::from_fn(|i| {
+            self.squared_average[i] = self.decay * self.squared_average[i]
+                + (1.0 - self.decay) * gradients[i] * gradients[i];
+            self.learning_rate * gradients[i] / (self.squared_average[i].sqrt() + self.stabilizer)
+        })
+    }
+}
+
+/// Bias-corrected decaying averages of both the gradients and their squares
+pub struct Adam<const N: usize> {
+    pub learning_rate: f64,
+    pub beta1: f64,
+    pub beta2: f64,
+    pub stabilizer: f64,
+    average: [f64; N],
+    squared_average: [f64; N],
+    steps: i32,
+}
+
+impl<const N: usize> Adam<N> {
+    pub fn new(learning_rate: f64, beta1: f64, beta2: f64, stabilizer: f64) -> Self {
+        Self {
+            learning_rate,
+            beta1,
+            beta2,
+            stabilizer,
+            average: [0.0; N],
+            squared_average: [0.0; N],
+            steps: 0,
+        }
+    }
+}
+
+impl<const N: usize> Optimizer<N> for Adam<N> {
+    fn step(&mut self, gradients: [f64; N]) -> [f64; N] {
+        self.steps = self.steps.saturating_add(1);
+        let average_correction = 1.0 - self.beta1.powi(self.steps);
+        let squared_correction = 1.0 - self.beta2.powi(self.steps);
+        array::from_fn(|i| {
+            self.average[i] = self.beta1 * self.average[i] + (1.0 - self.beta1) * gradients[i];
+            self.squared_average[i] = self.beta2 * self.squared_average[i]
+                + (1.0 - self.beta2) * gradients[i] * gradients[i];
+            let average = self.average[i] / average_correction;
+            let squared_average = self.squared_average[i] / squared_correction;
+            self.learning_rate * average / (squared_average.sqrt() + self.stabilizer)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Adagrad, Adam, Momentum, Optimizer, RmsProp, Sgd};
+
+    /// Minimize (x - 3)^2 + 10 (y + 1)^2
+    fn minimize(mut optimizer: impl Optimizer<2>) -> [f64; 2] {
+        let mut parameters = [0.0, 0.0];
+        for _ in 0..5000 {
+            let [x, y] = parameters;
+            let adjustments = optimizer.step([-2.0 * (x - 3.0), -20.0 * (y + 1.0)]);
+            for (parameter, adjustment) in parameters.iter_mut().zip(adjustments) {
+                *parameter += adjustment;
+            }
+        }
+        parameters
+    }
+
+    #[test]
+    fn converge() {
+        let results = [
+            minimize(Sgd {
+                learning_rate: 0.01,
+            }),
+            minimize(Momentum::new(0.01, 0.9, false)),
+            minimize(Momentum::new(0.01, 0.9, true)),
+            minimize(Adagrad::new(0.5, 1e-8)),
+            minimize(RmsProp::new(0.001, 0.9, 1e-8)),
+            minimize(Adam::new(0.01, 0.9, 0.999, 1e-8)),
+        ];
+        for [x, y] in results {
+            assert!((x - 3.0).abs() < 1e-2, "x = {x}");
+            assert!((y + 1.0).abs() < 1e-2, "y = {y}");
+        }
+    }
+}
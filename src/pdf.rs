@@ -0,0 +1,402 @@
+//! Plotters backend drawing vector PDF files, which plotters has none of. Text is set in the
+//! standard Helvetica font, which PDF readers provide, so no font is embedded
+
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use plotters_backend::{
+    text_anchor::{HPos, VPos},
+    BackendColor, BackendCoord, BackendStyle, BackendTextStyle, DrawingBackend, DrawingErrorKind,
+    FontTransform,
+};
+
+/// Widths of the printable ASCII characters in Helvetica, in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Control points of a quarter circle of radius 1 drawn as a cubic Bézier curve
+const KAPPA: f64 = 0.552_284_749_8;
+
+/// Backend writing a single page PDF to a file when presented or dropped, one point per pixel
+pub struct PdfBackend {
+    path: PathBuf,
+    size: (u32, u32),
+    content: String,
+    /// Opacities of the graphics states the content uses, named `/GS` and their index
+    opacities: Vec<f64>,
+    saved: bool,
+}
+
+impl PdfBackend {
+    pub fn new(path: &Path, size: (u32, u32)) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            size,
+            content: String::new(),
+            opacities: Vec::new(),
+            saved: false,
+        }
+    }
+
+    /// PDF's origin is the bottom left corner, where plotters' is the top left
+    fn point(&self, (x, y): BackendCoord) -> (i32, i32) {
+        (x, self.size.1 as i32 - y)
+    }
+
+    /// Set the color, and its opacity, of fills, or of strokes `width` wide
+    fn paint(&mut self, color: BackendColor, stroke: Option<u32>) {
+        let opacity = (color.alpha.clamp(0.0, 1.0) * 1000.0).round() / 1000.0;
+        let state = match self.opacities.iter().position(|o| *o == opacity) {
+            Some(state) => state,
+            None => {
+                self.opacities.push(opacity);
+                self.opacities.len() - 1
+            }
+        };
+        let (r, g, b) = color.rgb;
+        let [r, g, b] = [r, g, b].map(|channel| channel as f64 / 255.0);
+        let operator = match stroke {
+            Some(width) => {
+                let _ = write!(self.content, "{width} w ");
+                "RG"
+            }
+            None => "rg",
+        };
+        let _ = writeln!(
+            self.content,
+            "/GS{state} gs {r:.3} {g:.3} {b:.3} {operator}"
+        );
+    }
+
+    /// Add a path through the points, closing it if it's to be filled
+    fn path(&mut self, points: impl IntoIterator<Item = BackendCoord>, fill: bool) {
+        for (i, point) in points.into_iter().enumerate() {
+            let (x, y) = self.point(point);
+            let operator = if i == 0 { "m" } else { "l" };
+            let _ = writeln!(self.content, "{x} {y} {operator}");
+        }
+        self.content.push_str(if fill { "h f\n" } else { "S\n" });
+    }
+
+    /// The whole file, with the content drawn so far
+    fn document(&self) -> String {
+        let (width, height) = self.size;
+        let states = self
+            .opacities
+            .iter()
+            .enumerate()
+            .map(|(i, opacity)| format!("/GS{i} << /ca {opacity} /CA {opacity} >>"));
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {width} {height}] /Resources << \
+                 /Font << /F1 5 0 R >> /ExtGState << {} >> >> /Contents 4 0 R >>",
+                states.collect::<Vec<_>>().join(" ")
+            ),
+            format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                self.content.len(),
+                self.content
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+        ];
+        let mut document = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(document.len());
+            let _ = write!(document, "{} 0 obj\n{object}\nendobj\n", i + 1);
+        }
+        let xref = document.len();
+        let _ = write!(
+            document,
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        );
+        for offset in offsets {
+            let _ = writeln!(document, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            document,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1
+        );
+        document
+    }
+}
+
+/// Font size in points of text styled `size` pixels high, scaled like the SVG backend's
+fn font_size(style: &impl BackendTextStyle) -> f64 {
+    style.size() / 1.24
+}
+
+/// Width of text in Helvetica of the given size
+fn text_width(text: &str, size: f64) -> f64 {
+    let width = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => HELVETICA_WIDTHS[c as usize - ' ' as usize],
+            _ => 556,
+        })
+        .map(f64::from)
+        .sum::<f64>();
+    width * size / 1000.0
+}
+
+/// Text as a PDF string in the WinAnsi encoding, which covers Latin-1, writing other
+/// characters as `?`
+fn pdf_string(text: &str) -> String {
+    let mut string = String::from("(");
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                string.push('\\');
+                string.push(c);
+            }
+            ' '..='~' => string.push(c),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(string, "\\{:03o}", c as u32);
+            }
+            _ => string.push('?'),
+        }
+    }
+    string.push(')');
+    string
+}
+
+impl DrawingBackend for PdfBackend {
+    type ErrorType = io::Error;
+
+    fn get_size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn ensure_prepared(&mut self) -> Result<(), DrawingErrorKind<io::Error>> {
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), DrawingErrorKind<io::Error>> {
+        fs::write(&self.path, self.document()).map_err(DrawingErrorKind::DrawingError)?;
+        self.saved = true;
+        Ok(())
+    }
+
+    fn draw_pixel(
+        &mut self,
+        point: BackendCoord,
+        color: BackendColor,
+    ) -> Result<(), DrawingErrorKind<io::Error>> {
+        if color.alpha > 0.0 {
+            self.paint(color, None);
+            let (x, y) = self.point(point);
+            let _ = writeln!(self.content, "{x} {} 1 1 re f", y - 1);
+        }
+        Ok(())
+    }
+
+    fn draw_line<S: BackendStyle>(
+        &mut self,
+        from: BackendCoord,
+        to: BackendCoord,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<io::Error>> {
+        self.draw_path([from, to], style)
+    }
+
+    fn draw_rect<S: BackendStyle>(
+        &mut self,
+        upper_left: BackendCoord,
+        bottom_right: BackendCoord,
+        style: &S,
+        fill: bool,
+    ) -> Result<(), DrawingErrorKind<io::Error>> {
+        if style.color().alpha > 0.0 {
+            self.paint(style.color(), (!fill).then(|| style.stroke_width()));
+            let (x, y) = self.point((upper_left.0, bottom_right.1));
+            let (width, height) = (bottom_right.0 - upper_left.0, bottom_right.1 - upper_left.1);
+            let operator = if fill { "f" } else { "S" };
+            let _ = writeln!(self.content, "{x} {y} {width} {height} re {operator}");
+        }
+        Ok(())
+    }
+
+    fn draw_path<S: BackendStyle, I: IntoIterator<Item = BackendCoord>>(
+        &mut self,
+        path: I,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<io::Error>> {
+        if style.color().alpha > 0.0 {
+            self.paint(style.color(), Some(style.stroke_width()));
+            self.path(path, false);
+        }
+        Ok(())
+    }
+
+    fn fill_polygon<S: BackendStyle, I: IntoIterator<Item = BackendCoord>>(
+        &mut self,
+        vertices: I,
+        style: &S,
+    ) -> Result<(), DrawingErrorKind<io::Error>> {
+        if style.color().alpha > 0.0 {
+            self.paint(style.color(), None);
+            self.path(vertices, true);
+        }
+        Ok(())
+    }
+
+    fn draw_circle<S: BackendStyle>(
+        &mut self,
+        center: BackendCoord,
+        radius: u32,
+        style: &S,
+        fill: bool,
+    ) -> Result<(), DrawingErrorKind<io::Error>> {
+        if style.color().alpha > 0.0 {
+            self.paint(style.color(), (!fill).then(|| style.stroke_width()));
+            let (x, y) = self.point(center);
+            let (x, y, r) = (x as f64, y as f64, radius as f64);
+            let k = KAPPA * r;
+            let _ = writeln!(self.content, "{} {y} m", x + r);
+            // a quarter of the circle at a time, counterclockwise from the right
+            for quarter in 0..4 {
+                let [start, end] = [quarter, quarter + 1].map(|i| {
+                    let angle = i as f64 * std::f64::consts::FRAC_PI_2;
+                    (angle.cos().round(), angle.sin().round())
+                });
+                let first = (x + r * start.0 - k * start.1, y + r * start.1 + k * start.0);
+                let second = (x + r * end.0 + k * end.1, y + r * end.1 - k * end.0);
+                let end = (x + r * end.0, y + r * end.1);
+                let _ = writeln!(
+                    self.content,
+                    "{:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c",
+                    first.0, first.1, second.0, second.1, end.0, end.1
+                );
+            }
+            self.content.push_str(if fill { "h f\n" } else { "h S\n" });
+        }
+        Ok(())
+    }
+
+    fn draw_text<TStyle: BackendTextStyle>(
+        &mut self,
+        text: &str,
+        style: &TStyle,
+        pos: BackendCoord,
+    ) -> Result<(), DrawingErrorKind<io::Error>> {
+        if style.color().alpha == 0.0 {
+            return Ok(());
+        }
+        self.paint(style.color(), None);
+        let size = font_size(style);
+        let width = text_width(text, size);
+        // offsets along and across the text from the anchor to the start of its baseline
+        let along = match style.anchor().h_pos {
+            HPos::Left => 0.0,
+            HPos::Center => -width / 2.0,
+            HPos::Right => -width,
+        };
+        let across = match style.anchor().v_pos {
+            VPos::Top => -0.76 * size,
+            VPos::Center => -0.26 * size,
+            VPos::Bottom => 0.26 * size,
+        };
+        // plotters rotates clockwise on a page whose y axis points down
+        let (cos, sin) = match style.transform() {
+            FontTransform::Rotate90 => (0, -1),
+            FontTransform::Rotate180 => (-1, 0),
+            FontTransform::Rotate270 => (0, 1),
+            _ => (1, 0),
+        };
+        let (x, y) = self.point(pos);
+        let _ = writeln!(
+            self.content,
+            "BT /F1 {size:.2} Tf {cos} {sin} {} {cos} {x} {y} Tm {along:.2} {across:.2} Td {} Tj ET",
+            -sin,
+            pdf_string(text)
+        );
+        Ok(())
+    }
+
+    fn estimate_text_size<TStyle: BackendTextStyle>(
+        &self,
+        text: &str,
+        style: &TStyle,
+    ) -> Result<(u32, u32), DrawingErrorKind<io::Error>> {
+        let size = font_size(style);
+        Ok((text_width(text, size).ceil() as u32, size.ceil() as u32))
+    }
+}
+
+impl Drop for PdfBackend {
+    fn drop(&mut self) {
+        if !self.saved {
+            let _ = self.present();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use plotters::prelude::*;
+
+    use super::{pdf_string, text_width, PdfBackend};
+
+    #[test]
+    fn writes_pdf() {
+        let path = env::temp_dir().join(format!("plot-{}.pdf", std::process::id()));
+        {
+            let root = PdfBackend::new(&path, (320, 240)).into_drawing_area();
+            root.fill(&WHITE).unwrap();
+            let mut chart = ChartBuilder::on(&root)
+                .caption("fit (y)", ("sans-serif", 20))
+                .x_label_area_size(30)
+                .y_label_area_size(30)
+                .build_cartesian_2d(0.0..1.0, 0.0..1.0)
+                .unwrap();
+            chart.configure_mesh().y_desc("y").draw().unwrap();
+            chart
+                .draw_series(LineSeries::new([(0.0, 0.0), (1.0, 1.0)], RED.mix(0.5)))
+                .unwrap();
+            chart
+                .draw_series([Circle::new((0.5, 0.5), 3, BLUE.filled())])
+                .unwrap();
+            root.present().unwrap();
+        }
+        let pdf = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("/MediaBox [0 0 320 240]"));
+        assert!(pdf.contains("(fit \\(y\\)) Tj"));
+        assert!(pdf.contains("/ca 0.5"));
+        // the y axis description is rotated a quarter turn counterclockwise
+        assert!(pdf.contains("0 1 -1 0 "));
+
+        // every object is where the cross reference table says it is
+        let xref = pdf.find("xref\n").unwrap();
+        let offsets = pdf[xref..].lines().skip(3).take(5);
+        for (i, offset) in offsets.enumerate() {
+            let offset = offset[..10].parse::<usize>().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+        }
+        let start = pdf.rfind("startxref\n").unwrap() + "startxref\n".len();
+        assert_eq!(pdf[start..].lines().next(), Some(xref.to_string().as_str()));
+    }
+
+    #[test]
+    fn sets_text() {
+        assert_eq!(pdf_string("a(b)\\ ²σ"), "(a\\(b\\)\\\\ \\262?)");
+        assert!((text_width("Hi", 10.0) - 9.44).abs() < 1e-9);
+    }
+}
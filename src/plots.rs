@@ -0,0 +1,1028 @@
+use std::{
+    error::Error,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use plotters::{
+    coord::{
+        ranged1d::{AsRangedCoord, ValueFormatter},
+        Shift,
+    },
+    prelude::*,
+};
+
+use crate::{
+    history::{Entry, History},
+    pdf::PdfBackend,
+    statistics::normal_quantile,
+};
+
+/// File format plots are written in
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum PlotFormat {
+    /// Raster PNG image
+    Png,
+    /// Raster JPEG image
+    Jpeg,
+    /// Raster bitmap image
+    Bmp,
+    /// Scalable vector graphics
+    Svg,
+    /// Vector PDF document
+    Pdf,
+}
+
+impl PlotFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            PlotFormat::Png => "png",
+            PlotFormat::Jpeg => "jpg",
+            PlotFormat::Bmp => "bmp",
+            PlotFormat::Svg => "svg",
+            PlotFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// Appearance of the plots
+#[derive(Clone, Debug)]
+pub struct PlotStyle {
+    pub format: PlotFormat,
+    pub size: (u32, u32),
+    pub data_color: RGBColor,
+    pub fit_color: RGBColor,
+    pub point_size: u32,
+    pub line_width: u32,
+    pub title: Option<String>,
+    pub x_label: String,
+    pub y_label: String,
+    pub log_x: bool,
+    pub log_y: bool,
+}
+
+/// Parse a color given as a name, such as `red`, or as hexadecimal `#rrggbb`
+pub fn parse_color(color: &str) -> Result<RGBColor, String> {
+    let named = match color.to_lowercase().as_str() {
+        "black" => Some(BLACK),
+        "white" => Some(WHITE),
+        "red" => Some(RED),
+        "green" => Some(GREEN),
+        "blue" => Some(BLUE),
+        "cyan" => Some(CYAN),
+        "magenta" => Some(MAGENTA),
+        "yellow" => Some(YELLOW),
+        _ => None,
+    };
+    if let Some(named) = named {
+        return Ok(named);
+    }
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+    };
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok(RGBColor(r, g, b)),
+        _ => Err(format!(
+            "`{color}` is neither a color name nor a hexadecimal color like #ff8000"
+        )),
+    }
+}
+
+/// Something that can be drawn onto any plotters backend
+pub trait Figure {
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &PlotStyle,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static;
+}
+
+/// Write a figure to `path`, with the extension of the style's format, returning the full path
+pub fn save(
+    figure: &impl Figure,
+    path: &Path,
+    style: &PlotStyle,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let path = path.with_extension(style.format.extension());
+    match style.format {
+        PlotFormat::Svg => {
+            let root = SVGBackend::new(&path, style.size).into_drawing_area();
+            root.fill(&WHITE)?;
+            figure.draw(&root, style)?;
+            root.present()?;
+        }
+        PlotFormat::Pdf => {
+            let root = PdfBackend::new(&path, style.size).into_drawing_area();
+            root.fill(&WHITE)?;
+            figure.draw(&root, style)?;
+            root.present()?;
+        }
+        PlotFormat::Png | PlotFormat::Jpeg | PlotFormat::Bmp => {
+            let root = BitMapBackend::new(&path, style.size).into_drawing_area();
+            root.fill(&WHITE)?;
+            figure.draw(&root, style)?;
+            root.present()?;
+        }
+    }
+    Ok(path)
+}
+
+/// Animated GIF of figures, one frame per figure added
+pub struct Animation {
+    root: DrawingArea<BitMapBackend<'static>, Shift>,
+}
+
+impl Animation {
+    /// Start an animation at `path`, showing each frame for `frame_delay` milliseconds
+    pub fn new(path: &Path, style: &PlotStyle, frame_delay: u32) -> Result<Self, Box<dyn Error>> {
+        let backend = BitMapBackend::gif(path.with_extension("gif"), style.size, frame_delay)?;
+        Ok(Self {
+            root: backend.into_drawing_area(),
+        })
+    }
+
+    pub fn add_frame(&self, figure: &impl Figure, style: &PlotStyle) -> Result<(), Box<dyn Error>> {
+        self.root.fill(&WHITE)?;
+        figure.draw(&self.root, style)?;
+        self.root.present()?;
+        Ok(())
+    }
+}
+
+/// Range covering every value, padded by a tenth of its width on each side
+fn padded_range(values: impl Iterator<Item = f64> + Clone) -> Range<f64> {
+    let min = values.clone().fold(f64::INFINITY, f64::min);
+    let max = values.fold(f64::NEG_INFINITY, f64::max);
+    let padding = match (max - min) / 10.0 {
+        padding if padding > 0.0 => padding,
+        _ => 1.0,
+    };
+    min - padding..max + padding
+}
+
+/// Range covering every positive value, padded by a tenth of its width on each side on a
+/// logarithmic scale
+fn padded_log_range(values: impl Iterator<Item = f64> + Clone) -> Range<f64> {
+    let logs = padded_range(values.filter(|value| *value > 0.0).map(f64::log10));
+    if logs.start.is_finite() && logs.end.is_finite() {
+        10f64.powf(logs.start)..10f64.powf(logs.end)
+    } else {
+        0.1..10.0
+    }
+}
+
+/// `samples` evenly spaced values spanning `range`, or evenly spaced on a logarithmic scale if
+/// `log` is set, in which case the range must be positive
+pub fn grid(range: Range<f64>, samples: usize, log: bool) -> Vec<f64> {
+    let (start, end) = if log {
+        (range.start.ln(), range.end.ln())
+    } else {
+        (range.start, range.end)
+    };
+    let step = (end - start) / samples.saturating_sub(1).max(1) as f64;
+    (0..samples)
+        .map(|i| start + i as f64 * step)
+        .map(|value| if log { value.exp() } else { value })
+        .collect()
+}
+
+/// Value of a band around the fitted curve at one `x`
+#[derive(Clone, Debug)]
+pub struct Band {
+    pub x: f64,
+    pub y: f64,
+    /// Half width of the confidence band for the mean curve
+    pub confidence: f64,
+    /// Half width of the prediction band for new observations, if the uncertainty of an
+    /// observation at `x` is known
+    pub prediction: Option<f64>,
+}
+
+/// The data and the curve fitted to it, with optional confidence and prediction bands
+pub struct FitPlot<'a> {
+    pub data: &'a [(f64, f64)],
+    /// Standard deviation of each datum, drawn as error bars
+    pub sigmas: Option<&'a [f64]>,
+    /// Fitted curve in increasing order of `x`
+    pub curve: Vec<(f64, f64)>,
+    /// Equation of the curve, shown in the legend
+    pub equation: String,
+    /// Bands in increasing order of `x`
+    pub bands: Option<Vec<Band>>,
+    /// Plotted area for linear axes
+    pub bounds: (Range<f64>, Range<f64>),
+}
+
+impl FitPlot<'_> {
+    fn draw_on<'a, DB, X, Y>(
+        &self,
+        plot: &mut ChartContext<'a, DB, Cartesian2d<X, Y>>,
+        style: &PlotStyle,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend + 'a,
+        DB::ErrorType: 'static,
+        X: Ranged<ValueType = f64> + ValueFormatter<f64>,
+        Y: Ranged<ValueType = f64> + ValueFormatter<f64>,
+    {
+        plot.configure_mesh()
+            .x_desc(&style.x_label)
+            .y_desc(&style.y_label)
+            .draw()?;
+
+        // points with nonpositive coordinates on logarithmic axes can't be placed
+        let visible =
+            |(x, y): &(f64, f64)| (!style.log_x || *x > 0.0) && (!style.log_y || *y > 0.0);
+
+        // bands and error bars reaching below a logarithmic axis are cut off at its bottom
+        let y_floor = if style.log_y {
+            plot.y_range().start
+        } else {
+            f64::NEG_INFINITY
+        };
+
+        // shade the prediction band, if known everywhere, and within it the confidence band of the
+        // mean curve, leaving out any point whose margins couldn't be computed
+        let bands = self.bands.iter().flatten().filter(|b| {
+            [b.x, b.y, b.confidence, b.prediction.unwrap_or(0.0)]
+                .iter()
+                .all(|value| value.is_finite())
+        });
+        let bands = bands.collect::<Vec<_>>();
+        if !bands.is_empty() {
+            let band = |margin: fn(&Band) -> f64| {
+                let upper = bands.iter().map(|b| (b.x, b.y + margin(b)));
+                let lower = bands.iter().map(|b| (b.x, (b.y - margin(b)).max(y_floor)));
+                upper.chain(lower.rev()).filter(visible).collect::<Vec<_>>()
+            };
+            let prediction_color = style.fit_color.mix(0.12);
+            let confidence_color = style.fit_color.mix(0.3);
+            if bands.iter().all(|b| b.prediction.is_some()) {
+                let prediction = band(|b| b.prediction.unwrap_or_default());
+                plot.draw_series([Polygon::new(prediction, prediction_color)])?
+                    .label("prediction band")
+                    .legend(move |(x, y)| {
+                        Rectangle::new([(x, y - 5), (x + 20, y + 5)], prediction_color.filled())
+                    });
+            }
+            plot.draw_series([Polygon::new(band(|b| b.confidence), confidence_color)])?
+                .label("confidence band")
+                .legend(move |(x, y)| {
+                    Rectangle::new([(x, y - 5), (x + 20, y + 5)], confidence_color.filled())
+                });
+        }
+
+        let data_color = style.data_color;
+        if let Some(sigmas) = self.sigmas {
+            plot.draw_series(
+                self.data
+                    .iter()
+                    .zip(sigmas)
+                    .filter(|(point, sigma)| visible(point) && sigma.is_finite())
+                    .map(|(&(x, y), sigma)| {
+                        ErrorBar::new_vertical(
+                            x,
+                            (y - sigma).max(y_floor),
+                            y,
+                            y + sigma,
+                            data_color.stroke_width(1),
+                            2 * style.point_size + 2,
+                        )
+                    }),
+            )?;
+        }
+        plot.draw_series(
+            self.data
+                .iter()
+                .copied()
+                .filter(visible)
+                .map(|point| Circle::new(point, style.point_size, data_color.filled())),
+        )?
+        .label("data")
+        .legend(move |point| Circle::new(point, 4, data_color.filled()));
+        // plotters squashes points outside the chart onto its edges, so break the curve into the
+        // pieces that stay inside instead
+        let fit_style = style.fit_color.stroke_width(style.line_width);
+        let (x_range, y_range) = (plot.x_range(), plot.y_range());
+        let inside = |(x, y): &(f64, f64)| x_range.contains(x) && y_range.contains(y);
+        for piece in self.curve.split(|point| !inside(point)) {
+            plot.draw_series(LineSeries::new(piece.iter().copied(), fit_style))?;
+        }
+        plot.draw_series(LineSeries::new(std::iter::empty(), fit_style))?
+            .label(format!("fit: {}", self.equation))
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], fit_style));
+
+        plot.configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        Ok(())
+    }
+
+    fn draw_with<DB, X, Y>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &PlotStyle,
+        x_spec: X,
+        y_spec: Y,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+        X: AsRangedCoord<Value = f64>,
+        Y: AsRangedCoord<Value = f64>,
+        X::CoordDescType: ValueFormatter<f64>,
+        Y::CoordDescType: ValueFormatter<f64>,
+    {
+        let mut builder = ChartBuilder::on(root);
+        builder
+            .margin(5)
+            .x_label_area_size(40)
+            .y_label_area_size(50);
+        if let Some(title) = &style.title {
+            builder.caption(title, ("sans-serif", 30));
+        }
+        let mut plot = builder.build_cartesian_2d(x_spec, y_spec)?;
+        self.draw_on(&mut plot, style)
+    }
+}
+
+impl Figure for FitPlot<'_> {
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &PlotStyle,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        let (x_range, y_range) = self.bounds.clone();
+        let log_x_range = || {
+            let xs = self.data.iter().chain(&self.curve).map(|point| point.0);
+            padded_log_range(xs)
+        };
+        let log_y_range = || padded_log_range(self.data.iter().map(|point| point.1));
+        match (style.log_x, style.log_y) {
+            (false, false) => self.draw_with(root, style, x_range, y_range),
+            (true, false) => self.draw_with(root, style, log_x_range().log_scale(), y_range),
+            (false, true) => self.draw_with(root, style, x_range, log_y_range().log_scale()),
+            (true, true) => self.draw_with(
+                root,
+                style,
+                log_x_range().log_scale(),
+                log_y_range().log_scale(),
+            ),
+        }
+    }
+}
+
+/// Model of two inputs drawn as a heatmap, with a color bar, and the data drawn over it as points
+/// colored the same way
+pub struct SurfacePlot<'a> {
+    /// Inputs and observed value of each datum
+    pub data: &'a [([f64; 2], f64)],
+    /// Predictions over a grid of cells spanning `bounds`, by row of the first input
+    pub surface: Vec<Vec<f64>>,
+    /// Equation of the surface, shown in the legend
+    pub equation: String,
+    /// Plotted area
+    pub bounds: (Range<f64>, Range<f64>),
+}
+
+impl Figure for SurfacePlot<'_> {
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &PlotStyle,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        let (x_range, y_range) = self.bounds.clone();
+        let values = self
+            .surface
+            .iter()
+            .flatten()
+            .chain(self.data.iter().map(|(_, value)| value))
+            .copied()
+            .filter(|value| value.is_finite());
+        let min = values.clone().fold(f64::INFINITY, f64::min);
+        let max = values.fold(f64::NEG_INFINITY, f64::max);
+        let (min, max) = if min < max {
+            (min, max)
+        } else {
+            (min - 1.0, min + 1.0)
+        };
+        let color = |value: f64| ViridisRGB::get_color_normalized(value, min, max);
+
+        let (area, bar) = root.split_horizontally(style.size.0.saturating_sub(120));
+        let mut builder = ChartBuilder::on(&area);
+        builder
+            .margin(5)
+            .x_label_area_size(40)
+            .y_label_area_size(50);
+        if let Some(title) = &style.title {
+            builder.caption(title, ("sans-serif", 30));
+        }
+        let mut plot = builder.build_cartesian_2d(x_range.clone(), y_range.clone())?;
+        plot.configure_mesh()
+            .disable_mesh()
+            .x_desc("x0")
+            .y_desc("x1")
+            .draw()?;
+        let rows = self.surface.len().max(1) as f64;
+        let columns = self.surface.first().map_or(0, Vec::len).max(1) as f64;
+        let width = (x_range.end - x_range.start) / rows;
+        let height = (y_range.end - y_range.start) / columns;
+        plot.draw_series(self.surface.iter().enumerate().flat_map(|(i, row)| {
+            row.iter().enumerate().map(move |(j, value)| {
+                let x = x_range.start + i as f64 * width;
+                let y = y_range.start + j as f64 * height;
+                Rectangle::new([(x, y), (x + width, y + height)], color(*value).filled())
+            })
+        }))?;
+        let size = style.point_size + 1;
+        plot.draw_series(self.data.iter().map(|([x, y], value)| {
+            EmptyElement::at((*x, *y))
+                + Circle::new((0, 0), size, color(*value).filled())
+                + Circle::new((0, 0), size, BLACK.stroke_width(1))
+        }))?
+        .label(format!("data; fit: {}", self.equation))
+        .legend(move |point| Circle::new(point, 4, BLACK.stroke_width(1)));
+        plot.configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+
+        let mut bar = ChartBuilder::on(&bar)
+            .margin(5)
+            .margin_top(if style.title.is_some() { 45 } else { 5 })
+            .x_label_area_size(40)
+            .y_label_area_size(70)
+            .build_cartesian_2d(0.0..1.0, min..max)?;
+        // default text sizes are relative to the area, which is too narrow for them
+        bar.configure_mesh()
+            .disable_mesh()
+            .disable_x_axis()
+            .y_label_style(("sans-serif", 12))
+            .axis_desc_style(("sans-serif", 12))
+            .y_desc(&style.y_label)
+            .draw()?;
+        let steps = 256;
+        let step = (max - min) / steps as f64;
+        bar.draw_series((0..steps).map(|i| {
+            let value = min + i as f64 * step;
+            Rectangle::new(
+                [(0.0, value), (1.0, value + step)],
+                color(value + step / 2.0).filled(),
+            )
+        }))?;
+        Ok(())
+    }
+}
+
+/// Predicted against observed values, for models with too many inputs to plot directly; a
+/// perfect fit lies on the diagonal
+pub struct ParityPlot<'a> {
+    pub observed: &'a [f64],
+    pub predicted: Vec<f64>,
+    /// Equation of the model, shown in the legend
+    pub equation: String,
+}
+
+impl Figure for ParityPlot<'_> {
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &PlotStyle,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        let range = padded_range(
+            self.observed
+                .iter()
+                .chain(&self.predicted)
+                .copied()
+                .filter(|value| value.is_finite()),
+        );
+        let mut builder = ChartBuilder::on(root);
+        builder
+            .margin(5)
+            .x_label_area_size(40)
+            .y_label_area_size(50);
+        if let Some(title) = &style.title {
+            builder.caption(title, ("sans-serif", 30));
+        }
+        let mut plot = builder.build_cartesian_2d(range.clone(), range.clone())?;
+        plot.configure_mesh()
+            .x_desc(format!("observed {}", style.y_label))
+            .y_desc(format!("predicted {}", style.y_label))
+            .draw()?;
+        plot.draw_series(LineSeries::new(
+            [(range.start, range.start), (range.end, range.end)],
+            BLACK.stroke_width(1),
+        ))?;
+        let data_color = style.data_color;
+        plot.draw_series(
+            self.observed
+                .iter()
+                .copied()
+                .zip(self.predicted.iter().copied())
+                .map(|point| Circle::new(point, style.point_size, data_color.filled())),
+        )?
+        .label(format!("fit: {}", self.equation))
+        .legend(move |point| Circle::new(point, 4, data_color.filled()));
+        plot.configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        Ok(())
+    }
+}
+
+/// Scatter plot of `points` with a horizontal line at zero
+fn residual_scatter<DB>(
+    area: &DrawingArea<DB, Shift>,
+    style: &PlotStyle,
+    caption: &str,
+    (x_label, y_label): (&str, &str),
+    points: Vec<(f64, f64)>,
+) -> Result<(), Box<dyn Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let x_range = padded_range(points.iter().map(|point| point.0));
+    let y_range = padded_range(points.iter().map(|point| point.1));
+    let mut plot = ChartBuilder::on(area)
+        .caption(caption, ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(x_range.clone(), y_range)?;
+    plot.configure_mesh()
+        .x_desc(x_label)
+        .y_desc(y_label)
+        .draw()?;
+    plot.draw_series(LineSeries::new(
+        [(x_range.start, 0.0), (x_range.end, 0.0)],
+        BLACK.stroke_width(1),
+    ))?;
+    plot.draw_series(
+        points
+            .into_iter()
+            .map(|point| Circle::new(point, style.point_size, style.data_color.filled())),
+    )?;
+    Ok(())
+}
+
+/// Histogram of the residuals, with the square root of their count as the number of bins
+fn residual_histogram<DB>(
+    area: &DrawingArea<DB, Shift>,
+    style: &PlotStyle,
+    residuals: &[f64],
+    label: &str,
+) -> Result<(), Box<dyn Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let range = padded_range(residuals.iter().copied());
+    let bins = (residuals.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let width = (range.end - range.start) / bins as f64;
+    let mut counts = vec![0usize; bins];
+    for residual in residuals {
+        let bin = ((residual - range.start) / width) as usize;
+        counts[bin.min(bins - 1)] += 1;
+    }
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    let mut plot = ChartBuilder::on(area)
+        .caption("residual histogram", ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(range.clone(), 0.0..(max_count as f64 * 1.1).max(1.0))?;
+    plot.configure_mesh().x_desc(label).y_desc("count").draw()?;
+    plot.draw_series(counts.iter().enumerate().map(|(i, count)| {
+        let start = range.start + i as f64 * width;
+        Rectangle::new(
+            [(start, 0.0), (start + width, *count as f64)],
+            style.data_color.mix(0.5).filled(),
+        )
+    }))?;
+    Ok(())
+}
+
+/// Standardized residuals in increasing order against the quantiles of the normal distribution
+/// they would be expected at if the residuals were normally distributed
+fn normal_quantile_plot<DB>(
+    area: &DrawingArea<DB, Shift>,
+    style: &PlotStyle,
+    residuals: &[f64],
+) -> Result<(), Box<dyn Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let n = residuals.len() as f64;
+    let mean = residuals.iter().sum::<f64>() / n;
+    let std = (residuals
+        .iter()
+        .map(|r| (r - mean) * (r - mean))
+        .sum::<f64>()
+        / (n - 1.0))
+        .sqrt();
+    let mut standardized = residuals
+        .iter()
+        .map(|r| (r - mean) / std)
+        .collect::<Vec<_>>();
+    standardized.sort_by(f64::total_cmp);
+    let points = standardized
+        .into_iter()
+        .enumerate()
+        .map(|(i, residual)| (normal_quantile((i as f64 + 0.5) / n), residual))
+        .collect::<Vec<_>>();
+    let x_range = padded_range(points.iter().map(|point| point.0));
+    let y_range = padded_range(points.iter().map(|point| point.1));
+    let mut plot = ChartBuilder::on(area)
+        .caption("normal Q-Q", ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(x_range.clone(), y_range)?;
+    plot.configure_mesh()
+        .x_desc("theoretical quantile")
+        .y_desc("standardized residual")
+        .draw()?;
+    plot.draw_series(LineSeries::new(
+        [(x_range.start, x_range.start), (x_range.end, x_range.end)],
+        BLACK.stroke_width(1),
+    ))?;
+    plot.draw_series(
+        points
+            .into_iter()
+            .map(|point| Circle::new(point, style.point_size, style.data_color.filled())),
+    )?;
+    Ok(())
+}
+
+/// Residuals against `x`, residuals against the `fitted` values, a histogram of the residuals
+/// and their normal Q-Q plot
+pub struct ResidualDiagnostics<'a> {
+    pub x: &'a [f64],
+    pub fitted: &'a [f64],
+    pub residuals: &'a [f64],
+    /// What the residuals are, such as `weighted residual` for residuals scaled by their weights
+    pub label: &'a str,
+}
+
+impl Figure for ResidualDiagnostics<'_> {
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &PlotStyle,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        let areas = root.split_evenly((2, 2));
+        let against = |values: &[f64]| {
+            values
+                .iter()
+                .copied()
+                .zip(self.residuals.iter().copied())
+                .collect()
+        };
+        let label = self.label;
+        residual_scatter(
+            &areas[0],
+            style,
+            "residuals vs x",
+            ("x", label),
+            against(self.x),
+        )?;
+        residual_scatter(
+            &areas[1],
+            style,
+            "residuals vs fitted",
+            ("fitted value", label),
+            against(self.fitted),
+        )?;
+        residual_histogram(&areas[2], style, self.residuals, label)?;
+        normal_quantile_plot(&areas[3], style, self.residuals)?;
+        Ok(())
+    }
+}
+
+/// Figures stacked one above another, such as the plots of each output of a model, each with
+/// its own label for the y axis. The title is only drawn over the first
+pub struct Panels<F> {
+    pub figures: Vec<F>,
+    pub y_labels: Vec<String>,
+}
+
+impl<F: Figure> Figure for Panels<F> {
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &PlotStyle,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        let areas = root.split_evenly((self.figures.len(), 1));
+        for (i, ((figure, y_label), area)) in self
+            .figures
+            .iter()
+            .zip(&self.y_labels)
+            .zip(&areas)
+            .enumerate()
+        {
+            let style = PlotStyle {
+                size: area.dim_in_pixel(),
+                title: style.title.clone().filter(|_| i == 0),
+                y_label: y_label.clone(),
+                ..style.clone()
+            };
+            figure.draw(area, &style)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error and gradient magnitude against iteration on a logarithmic scale, with the trajectory of
+/// each parameter below it if `parameters` is set
+pub struct Convergence<'a, const N: usize> {
+    pub history: &'a History<N>,
+    pub parameters: bool,
+    /// Name of each parameter, for the legend
+    pub names: &'a [String; N],
+}
+
+impl<const N: usize> Figure for Convergence<'_, N> {
+    fn draw<DB>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        style: &PlotStyle,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        DB: DrawingBackend,
+        DB::ErrorType: 'static,
+    {
+        let history = self.history;
+        let (top, bottom) = if self.parameters {
+            let (top, bottom) = root.split_vertically(style.size.1 / 2);
+            (top, Some(bottom))
+        } else {
+            (root.clone(), None)
+        };
+        let last_iteration = history.entries.last().map_or(0, |entry| entry.iteration);
+        let iterations = 0.0..(last_iteration as f64).max(1.0);
+
+        // zero errors or magnitudes can't be shown on a logarithmic scale
+        let positive = history
+            .entries
+            .iter()
+            .flat_map(|entry| [entry.error, entry.magnitude.abs()])
+            .filter(|value| *value > 0.0 && value.is_finite());
+        let min = positive.clone().fold(f64::INFINITY, f64::min);
+        let max = positive.fold(f64::NEG_INFINITY, f64::max);
+        let (min, max) = if min <= max { (min, max) } else { (0.1, 10.0) };
+        let mut plot = ChartBuilder::on(&top)
+            .caption("convergence", ("sans-serif", 24))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(80)
+            .build_cartesian_2d(iterations.clone(), (min / 2.0..max * 2.0).log_scale())?;
+        plot.configure_mesh()
+            .x_desc("iteration")
+            .y_desc("value")
+            .draw()?;
+        let series = |value: fn(&Entry<N>) -> f64| {
+            history
+                .entries
+                .iter()
+                .map(move |entry| (entry.iteration as f64, value(entry)))
+                .filter(|(_, value)| *value > 0.0 && value.is_finite())
+        };
+        let line_width = style.line_width;
+        plot.draw_series(LineSeries::new(
+            series(|entry| entry.error),
+            RED.stroke_width(line_width),
+        ))?
+        .label("error")
+        .legend(move |(x, y)| {
+            PathElement::new([(x, y), (x + 20, y)], RED.stroke_width(line_width))
+        });
+        plot.draw_series(LineSeries::new(
+            series(|entry| entry.magnitude.abs()),
+            BLUE.stroke_width(line_width),
+        ))?
+        .label("gradient magnitude")
+        .legend(move |(x, y)| {
+            PathElement::new([(x, y), (x + 20, y)], BLUE.stroke_width(line_width))
+        });
+        plot.configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+
+        if let Some(bottom) = bottom {
+            let range = padded_range(
+                history
+                    .entries
+                    .iter()
+                    .flat_map(|entry| entry.parameters)
+                    .filter(|value| value.is_finite()),
+            );
+            let mut plot = ChartBuilder::on(&bottom)
+                .caption("parameters", ("sans-serif", 24))
+                .margin(10)
+                .x_label_area_size(40)
+                .y_label_area_size(80)
+                .build_cartesian_2d(iterations, range)?;
+            plot.configure_mesh()
+                .x_desc("iteration")
+                .y_desc("value")
+                .draw()?;
+            for i in 0..N {
+                let color = Palette99::pick(i).to_rgba();
+                plot.draw_series(LineSeries::new(
+                    history
+                        .entries
+                        .iter()
+                        .map(|entry| (entry.iteration as f64, entry.parameters[i])),
+                    color.stroke_width(line_width),
+                ))?
+                .label(&self.names[i])
+                .legend(move |(x, y)| {
+                    PathElement::new([(x, y), (x + 20, y)], color.stroke_width(line_width))
+                });
+            }
+            plot.configure_series_labels()
+                .background_style(WHITE.mix(0.8))
+                .border_style(BLACK)
+                .draw()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use plotters::style::{RGBColor, BLUE, RED};
+
+    use plotters::prelude::{IntoDrawingArea, SVGBackend};
+
+    use super::{
+        grid, parse_color, Animation, Band, Figure, FitPlot, PlotFormat, PlotStyle,
+        ResidualDiagnostics,
+    };
+
+    fn style() -> PlotStyle {
+        PlotStyle {
+            format: PlotFormat::Png,
+            size: (320, 240),
+            data_color: BLUE,
+            fit_color: RED,
+            point_size: 2,
+            line_width: 2,
+            title: Some("fit".into()),
+            x_label: "x".into(),
+            y_label: "y".into(),
+            log_x: false,
+            log_y: false,
+        }
+    }
+
+    #[test]
+    fn grids() {
+        assert_eq!(grid(0.0..1.0, 5, false), [0.0, 0.25, 0.5, 0.75, 1.0]);
+        let log = grid(1.0..1000.0, 4, true);
+        for (value, expected) in log.into_iter().zip([1.0, 10.0, 100.0, 1000.0]) {
+            assert!((value - expected).abs() < 1e-9 * expected);
+        }
+        assert_eq!(grid(2.0..3.0, 1, false), [2.0]);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("Red"), Ok(RGBColor(255, 0, 0)));
+        assert_eq!(parse_color("#ff8000"), Ok(RGBColor(255, 128, 0)));
+        assert_eq!(parse_color("0080FF"), Ok(RGBColor(0, 128, 255)));
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("purplish").is_err());
+    }
+
+    #[test]
+    fn labels_residuals() {
+        let x = [0.0, 1.0, 2.0, 3.0];
+        let figure = ResidualDiagnostics {
+            x: &x,
+            fitted: &[1.0, 3.0, 5.0, 7.0],
+            residuals: &[0.5, -1.0, 1.5, -0.5],
+            label: "normalized residual",
+        };
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, (320, 240)).into_drawing_area();
+            figure.draw(&root, &style()).unwrap();
+        }
+        assert_eq!(svg.matches("\nnormalized residual\n").count(), 3);
+    }
+
+    #[test]
+    fn skips_undefined_bands() {
+        let data = [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)];
+        let bands = [
+            (0.0, 0.1),
+            (0.5, f64::NAN),
+            (1.0, f64::INFINITY),
+            (2.0, 0.3),
+        ]
+        .map(|(x, margin)| Band {
+            x,
+            y: x,
+            confidence: margin,
+            prediction: Some(2.0 * margin),
+        })
+        .to_vec();
+        let figure = FitPlot {
+            data: &data,
+            sigmas: None,
+            curve: vec![(0.0, 0.0), (2.0, 2.0)],
+            equation: "x".into(),
+            bands: Some(bands),
+            bounds: (-0.5..2.5, -1.0..3.0),
+        };
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, (320, 240)).into_drawing_area();
+            figure.draw(&root, &style()).unwrap();
+        }
+        assert_eq!(svg.matches("<polygon").count(), 2);
+
+        // without the uncertainty of new observations, only the confidence band is drawn
+        let bands = figure.bands.as_ref().unwrap().iter();
+        let bands = bands.map(|band| Band {
+            prediction: None,
+            ..band.clone()
+        });
+        let figure = FitPlot {
+            bands: Some(bands.collect()),
+            ..figure
+        };
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, (320, 240)).into_drawing_area();
+            figure.draw(&root, &style()).unwrap();
+        }
+        assert_eq!(svg.matches("<polygon").count(), 1);
+    }
+
+    #[test]
+    fn animates() {
+        let path = env::temp_dir().join(format!("animation-{}", std::process::id()));
+        let data = [(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)];
+        let style = style();
+        let animation = Animation::new(&path, &style, 100).unwrap();
+        for slope in [0.0, 1.0, 2.0] {
+            let figure = FitPlot {
+                data: &data,
+                sigmas: None,
+                curve: vec![(0.0, 1.0), (2.0, 1.0 + 2.0 * slope)],
+                equation: format!("{slope} * x + 1"),
+                bands: None,
+                bounds: (-0.5..2.5, 0.0..6.0),
+            };
+            animation.add_frame(&figure, &style).unwrap();
+        }
+        drop(animation);
+        let gif = fs::read(path.with_extension("gif")).unwrap();
+        fs::remove_file(path.with_extension("gif")).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+        // each frame is a graphic control extension followed by an image descriptor
+        let frames = gif
+            .windows(9)
+            .filter(|w| w[..3] == [0x21, 0xf9, 0x04] && w[7] == 0 && w[8] == 0x2c)
+            .count();
+        assert_eq!(frames, 3);
+    }
+}
@@ -1,6 +1,19 @@
-use std::fmt::Display;
-
-use crate::GradientDescent;
+use std::{array, fmt::Display, marker::PhantomData};
+
+use crate::{
+    apply_nudge,
+    dual::{Dual, Scalar},
+    evaluate_polynomial,
+    expression::Expression,
+    finite_difference_jacobian, polynomial_derivatives, write_formula, write_polynomial,
+    GradientDescent,
+};
+
+/// Parameter values with the nudge, if any, applied
+fn nudged<const N: usize>(mut parameters: [f64; N], nudge: Option<(usize, f64)>) -> [f64; N] {
+    apply_nudge(&mut parameters, nudge);
+    parameters
+}
 
 #[derive(Clone, Debug)]
 pub struct Exponential {
@@ -8,6 +21,12 @@ pub struct Exponential {
     pub growth: f64,
 }
 
+impl Exponential {
+    fn evaluate<S: Scalar>([base, growth]: [S; 2], input: &[f64; 1]) -> S {
+        base * growth.powf(S::constant(input[0]))
+    }
+}
+
 impl Display for Exponential {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} * {} ^ x", self.base, self.growth)
@@ -33,18 +52,40 @@ impl GradientDescent for Exponential {
         nudge: Option<(usize, f64)>,
         input: &[f64; Self::IN_DIMENSION],
     ) -> [f64; Self::OUT_DIMENSION] {
-        [match nudge {
-            None => self.base * self.growth.powf(input[0]),
-            Some((0, epsilon)) => (self.base + epsilon) * self.growth.powf(input[0]),
-            Some((1, epsilon)) => self.base * (self.growth + epsilon).powf(input[0]),
-            _ => unreachable!(),
-        }]
+        [Self::evaluate(
+            nudged([self.base, self.growth], nudge),
+            input,
+        )]
+    }
+
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION] {
+        [self.base, self.growth]
+    }
+
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]) {
+        [self.base, self.growth] = parameters;
+    }
+
+    fn parameter_names(&self) -> [String; Self::PARAM_DIMENSION] {
+        ["base", "growth"].map(String::from)
     }
 
     fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]) {
         self.base += adjustments[0];
         self.growth += adjustments[1];
     }
+
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        _epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        let x = input[0];
+        [[
+            self.growth.powf(x),
+            self.base * x * self.growth.powf(x - 1.0),
+        ]]
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -53,6 +94,12 @@ pub struct Linear {
     pub y_intercept: f64,
 }
 
+impl Linear {
+    fn evaluate<S: Scalar>([slope, y_intercept]: [S; 2], input: &[f64; 1]) -> S {
+        slope * input[0] + y_intercept
+    }
+}
+
 impl Display for Linear {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} * x + {}", self.slope, self.y_intercept)
@@ -72,24 +119,121 @@ impl GradientDescent for Linear {
     const IN_DIMENSION: usize = 1;
     const PARAM_DIMENSION: usize = 2;
     const OUT_DIMENSION: usize = 1;
+    const LINEAR: bool = true;
 
     fn predict(
         &self,
         nudge: Option<(usize, f64)>,
         input: &[f64; Self::IN_DIMENSION],
     ) -> [f64; Self::OUT_DIMENSION] {
-        [match nudge {
-            None => self.slope * input[0] + self.y_intercept,
-            Some((0, epsilon)) => (self.slope + epsilon) * input[0] + self.y_intercept,
-            Some((1, epsilon)) => self.slope * input[0] + self.y_intercept + epsilon,
-            _ => unreachable!(),
-        }]
+        [Self::evaluate(
+            nudged([self.slope, self.y_intercept], nudge),
+            input,
+        )]
+    }
+
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION] {
+        [self.slope, self.y_intercept]
+    }
+
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]) {
+        [self.slope, self.y_intercept] = parameters;
+    }
+
+    fn parameter_names(&self) -> [String; Self::PARAM_DIMENSION] {
+        ["slope", "y_intercept"].map(String::from)
     }
 
     fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]) {
         self.slope += adjustments[0];
         self.y_intercept += adjustments[1];
     }
+
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        _epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        [[input[0], 1.0]]
+    }
+}
+
+/// Linear function of several inputs, `w₀x₀ + w₁x₁ + … + b`
+#[derive(Clone, Debug)]
+pub struct MultiLinear<const INPUTS: usize> {
+    pub weights: [f64; INPUTS],
+    pub intercept: f64,
+}
+
+impl<const INPUTS: usize> Display for MultiLinear<INPUTS> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, weight) in self.weights.iter().enumerate() {
+            write!(f, "{weight} * x{i} + ")?;
+        }
+        write!(f, "{}", self.intercept)
+    }
+}
+
+impl<const INPUTS: usize> Default for MultiLinear<INPUTS> {
+    fn default() -> Self {
+        Self {
+            weights: [1.0; INPUTS],
+            intercept: 0.0,
+        }
+    }
+}
+
+impl<const INPUTS: usize> GradientDescent for MultiLinear<INPUTS> {
+    const IN_DIMENSION: usize = INPUTS;
+    const PARAM_DIMENSION: usize = INPUTS + 1;
+    const OUT_DIMENSION: usize = 1;
+    const LINEAR: bool = true;
+
+    fn predict(
+        &self,
+        nudge: Option<(usize, f64)>,
+        input: &[f64; Self::IN_DIMENSION],
+    ) -> [f64; Self::OUT_DIMENSION] {
+        let (mut weights, mut intercept) = (self.weights, self.intercept);
+        match nudge {
+            Some((i, epsilon)) if i < INPUTS => weights[i] += epsilon,
+            Some((_, epsilon)) => intercept += epsilon,
+            None => {}
+        }
+        let output = intercept + weights.iter().zip(input).map(|(w, x)| w * x).sum::<f64>();
+        array::from_fn(|_| output)
+    }
+
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION] {
+        array::from_fn(|i| self.weights.get(i).copied().unwrap_or(self.intercept))
+    }
+
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]) {
+        self.weights = array::from_fn(|i| parameters[i]);
+        self.intercept = parameters[INPUTS];
+    }
+
+    fn parameter_names(&self) -> [String; Self::PARAM_DIMENSION] {
+        array::from_fn(|i| match i {
+            i if i < INPUTS => format!("w{i}"),
+            _ => "intercept".to_string(),
+        })
+    }
+
+    fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]) {
+        for (weight, adjustment) in self.weights.iter_mut().zip(adjustments) {
+            *weight += adjustment;
+        }
+        self.intercept += adjustments[INPUTS];
+    }
+
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        _epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        array::from_fn(|_| array::from_fn(|i| if i < INPUTS { input[i] } else { 1.0 }))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -99,30 +243,7 @@ pub struct Polynomial<const TERMS: usize> {
 
 impl<const TERMS: usize> Display for Polynomial<TERMS> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            self.terms
-                .iter()
-                .enumerate()
-                .rev()
-                .map(|(i, constant)| {
-                    let sign = match (i == TERMS - 1, constant.is_sign_positive()) {
-                        (true, true) => "",
-                        (true, false) => "-",
-                        (false, true) => " + ",
-                        (false, false) => " - ",
-                    };
-                    let term = match i {
-                        0 => "".to_string(),
-                        1 => "x".to_string(),
-                        n => format!("x^{n}"),
-                    };
-                    format!("{sign}{}{term}", constant.abs())
-                })
-                .collect::<Vec<_>>()
-                .join("")
-        )
+        write_polynomial(f, &self.terms)
     }
 }
 
@@ -138,22 +259,27 @@ impl<const TERMS: usize> GradientDescent for Polynomial<TERMS> {
     const IN_DIMENSION: usize = 1;
     const PARAM_DIMENSION: usize = TERMS;
     const OUT_DIMENSION: usize = 1;
+    const LINEAR: bool = true;
+
+    fn predict(
+        &self,
+        nudge: Option<(usize, f64)>,
+        input: &[f64; Self::IN_DIMENSION],
+    ) -> [f64; Self::OUT_DIMENSION] {
+        let output = evaluate_polynomial(nudged(self.terms, nudge), input[0]);
+        array::from_fn(|_| output)
+    }
+
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION] {
+        array::from_fn(|i| self.terms[i])
+    }
 
-    fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64; 1]) -> [f64; 1] {
-        [match nudge {
-            None => self
-                .terms
-                .iter()
-                .enumerate()
-                .map(|(i, constant)| constant * input[0].powi(i as i32))
-                .sum(),
-            Some((nudge, epsilon)) => self.terms
-                .iter()
-                .enumerate()
-                .map(|(i, constant)| if i == nudge { constant + epsilon } else { *constant } * input[0].powi(i as i32))
-                .sum()
-            ,
-        }]
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]) {
+        self.terms = array::from_fn(|i| parameters[i]);
+    }
+
+    fn parameter_names(&self) -> [String; Self::PARAM_DIMENSION] {
+        array::from_fn(|i| format!("a{i}"))
     }
 
     fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]) {
@@ -161,18 +287,30 @@ impl<const TERMS: usize> GradientDescent for Polynomial<TERMS> {
             *term += adjustment;
         }
     }
+
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        _epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        let derivatives = polynomial_derivatives(TERMS, input[0]).collect::<Vec<_>>();
+        array::from_fn(|_| array::from_fn(|i| derivatives[i]))
+    }
 }
 
+/// `function` may be written over any [`Scalar`] `S`; choosing `S = Dual<4>` gives the
+/// equation an exact [`GradientDescent::jacobian`]
 #[derive(Debug)]
-pub struct ScaledTranslatedEquation<F> {
+pub struct ScaledTranslatedEquation<F, S = f64> {
     pub x_0: f64,
     pub y_0: f64,
     pub width: f64,
     pub height: f64,
     pub function: F,
+    scalar: PhantomData<S>,
 }
 
-impl<F> ScaledTranslatedEquation<F> {
+impl<F, S> ScaledTranslatedEquation<F, S> {
     pub fn new(function: F) -> Self {
         Self {
             x_0: 0.0,
@@ -180,11 +318,19 @@ impl<F> ScaledTranslatedEquation<F> {
             width: 1.0,
             height: 1.0,
             function,
+            scalar: PhantomData,
         }
     }
+
+    fn evaluate<T: Scalar>(&self, [x_0, y_0, width, height]: [T; 4], input: &[f64; 1]) -> T
+    where
+        F: Fn(T) -> T,
+    {
+        (self.function)((T::constant(input[0]) - x_0) / width) * height + y_0
+    }
 }
 
-impl<F> Default for ScaledTranslatedEquation<F>
+impl<F, S> Default for ScaledTranslatedEquation<F, S>
 where
     F: Default,
 {
@@ -193,7 +339,7 @@ where
     }
 }
 
-impl<F> Display for ScaledTranslatedEquation<F> {
+impl<F, S> Display for ScaledTranslatedEquation<F, S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self {
             x_0,
@@ -206,9 +352,10 @@ impl<F> Display for ScaledTranslatedEquation<F> {
     }
 }
 
-impl<F> GradientDescent for ScaledTranslatedEquation<F>
+impl<F, S> GradientDescent for ScaledTranslatedEquation<F, S>
 where
-    F: Fn(f64) -> f64,
+    F: Fn(S) -> S,
+    S: Scalar,
 {
     const IN_DIMENSION: usize = 1;
 
@@ -216,25 +363,31 @@ where
 
     const OUT_DIMENSION: usize = 1;
 
-    fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64; 1]) -> [f64; 1] {
-        let x = input[0];
-        let output = match nudge {
-            None => (self.function)((x - self.x_0) / self.width) * self.height + self.y_0,
-            Some((0, epsilon)) => {
-                (self.function)((x - self.x_0 + epsilon) / self.width) * self.height + self.y_0
-            }
-            Some((1, epsilon)) => {
-                (self.function)((x - self.x_0) / self.width) * self.height + (self.y_0 + epsilon)
-            }
-            Some((2, epsilon)) => {
-                (self.function)((x - self.x_0) / (self.width + epsilon)) * self.height + self.y_0
-            }
-            Some((3, epsilon)) => {
-                (self.function)((x - self.x_0) / self.width) * (self.height + epsilon) + self.y_0
-            }
-            _ => unreachable!(),
-        };
-        [output]
+    fn predict(
+        &self,
+        nudge: Option<(usize, f64)>,
+        input: &[f64; Self::IN_DIMENSION],
+    ) -> [f64; Self::OUT_DIMENSION] {
+        let parameters = nudged([self.x_0, self.y_0, self.width, self.height], nudge);
+        let output = self.evaluate(parameters.map(S::constant), &[input[0]]);
+        array::from_fn(|_| output.value())
+    }
+
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION] {
+        let parameters = [self.x_0, self.y_0, self.width, self.height];
+        array::from_fn(|i| parameters[i])
+    }
+
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]) {
+        self.x_0 = parameters[0];
+        self.y_0 = parameters[1];
+        self.width = parameters[2];
+        self.height = parameters[3];
+    }
+
+    fn parameter_names(&self) -> [String; Self::PARAM_DIMENSION] {
+        let names = ["x_0", "y_0", "width", "height"];
+        array::from_fn(|i| names[i].to_string())
     }
 
     fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]) {
@@ -243,19 +396,36 @@ where
         self.width += adjustments[2];
         self.height += adjustments[3];
     }
+
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        if S::VARIABLES < Self::PARAM_DIMENSION {
+            return finite_difference_jacobian(self, input, epsilon);
+        }
+        let values = [self.x_0, self.y_0, self.width, self.height];
+        let parameters = array::from_fn(|i| S::variable(values[i], i));
+        let output = self.evaluate(parameters, &[input[0]]);
+        array::from_fn(|_| array::from_fn(|i| output.derivative(i)))
+    }
 }
 
+/// `function` may be written over any [`Scalar`] `S`; choosing `S = Dual<{ 4 + P }>` gives
+/// the equation an exact [`GradientDescent::jacobian`]
 #[derive(Debug)]
-pub struct ParametricScaledTranslatedEquation<F, const P: usize> {
+pub struct ParametricScaledTranslatedEquation<F, const P: usize, S = f64> {
     pub x_0: f64,
     pub y_0: f64,
     pub width: f64,
     pub height: f64,
     pub parameters: [f64; P],
     pub function: F,
+    scalar: PhantomData<S>,
 }
 
-impl<F, const P: usize> ParametricScaledTranslatedEquation<F, P> {
+impl<F, const P: usize, S> ParametricScaledTranslatedEquation<F, P, S> {
     pub fn new(function: F) -> Self {
         Self {
             x_0: 0.0,
@@ -264,11 +434,31 @@ impl<F, const P: usize> ParametricScaledTranslatedEquation<F, P> {
             height: 1.0,
             parameters: [0.0; P],
             function,
+            scalar: PhantomData,
         }
     }
+
+    /// `parameters` holds x_0, y_0, width and height followed by the function's own parameters
+    fn evaluate<T: Scalar>(&self, parameters: &[T], input: &[f64; 1]) -> T
+    where
+        F: Fn(T, [T; P]) -> T,
+    {
+        let [x_0, y_0, width, height] = [0, 1, 2, 3].map(|i| parameters[i]);
+        (self.function)(
+            (T::constant(input[0]) - x_0) / width,
+            array::from_fn(|i| parameters[4 + i]),
+        ) * height
+            + y_0
+    }
+
+    fn all_parameters(&self) -> Vec<f64> {
+        let mut parameters = vec![self.x_0, self.y_0, self.width, self.height];
+        parameters.extend(self.parameters);
+        parameters
+    }
 }
 
-impl<F, const P: usize> Default for ParametricScaledTranslatedEquation<F, P>
+impl<F, const P: usize, S> Default for ParametricScaledTranslatedEquation<F, P, S>
 where
     F: Default,
 {
@@ -277,7 +467,7 @@ where
     }
 }
 
-impl<F, const P: usize> Display for ParametricScaledTranslatedEquation<F, P> {
+impl<F, const P: usize, S> Display for ParametricScaledTranslatedEquation<F, P, S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self {
             x_0,
@@ -299,9 +489,10 @@ impl<F, const P: usize> Display for ParametricScaledTranslatedEquation<F, P> {
     }
 }
 
-impl<F, const P: usize> GradientDescent for ParametricScaledTranslatedEquation<F, P>
+impl<F, const P: usize, S> GradientDescent for ParametricScaledTranslatedEquation<F, P, S>
 where
-    F: Fn(f64, [f64; P]) -> f64,
+    F: Fn(S, [S; P]) -> S,
+    S: Scalar,
 {
     const IN_DIMENSION: usize = 1;
 
@@ -309,41 +500,37 @@ where
 
     const OUT_DIMENSION: usize = 1;
 
-    fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64; 1]) -> [f64; 1] {
-        let x = input[0];
-        let output = match nudge {
-            None => {
-                (self.function)((x - self.x_0) / self.width, self.parameters) * self.height
-                    + self.y_0
-            }
-            Some((0, epsilon)) => {
-                (self.function)((x - self.x_0 + epsilon) / self.width, self.parameters)
-                    * self.height
-                    + self.y_0
-            }
-            Some((1, epsilon)) => {
-                (self.function)((x - self.x_0) / self.width, self.parameters) * self.height
-                    + (self.y_0 + epsilon)
-            }
-            Some((2, epsilon)) => {
-                (self.function)((x - self.x_0) / (self.width + epsilon), self.parameters)
-                    * self.height
-                    + self.y_0
-            }
-            Some((3, epsilon)) => {
-                (self.function)((x - self.x_0) / self.width, self.parameters)
-                    * (self.height + epsilon)
-                    + self.y_0
-            }
-            Some((n, epsilon)) => {
-                let mut new_parameters = self.parameters.clone();
-                new_parameters[n - 4] += epsilon;
-                (self.function)((x - self.x_0) / self.width, new_parameters)
-                    * (self.height + epsilon)
-                    + self.y_0
-            }
-        };
-        [output]
+    fn predict(
+        &self,
+        nudge: Option<(usize, f64)>,
+        input: &[f64; Self::IN_DIMENSION],
+    ) -> [f64; Self::OUT_DIMENSION] {
+        let mut parameters = self.all_parameters();
+        apply_nudge(&mut parameters, nudge);
+        let parameters = parameters.into_iter().map(S::constant).collect::<Vec<_>>();
+        let output = self.evaluate(&parameters, &[input[0]]);
+        array::from_fn(|_| output.value())
+    }
+
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION] {
+        let parameters = self.all_parameters();
+        array::from_fn(|i| parameters[i])
+    }
+
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]) {
+        self.x_0 = parameters[0];
+        self.y_0 = parameters[1];
+        self.width = parameters[2];
+        self.height = parameters[3];
+        self.parameters = array::from_fn(|i| parameters[4 + i]);
+    }
+
+    fn parameter_names(&self) -> [String; Self::PARAM_DIMENSION] {
+        let names = ["x_0", "y_0", "width", "height"];
+        array::from_fn(|i| match names.get(i) {
+            Some(name) => name.to_string(),
+            None => format!("p{}", i - 4),
+        })
     }
 
     fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]) {
@@ -351,28 +538,50 @@ where
         self.y_0 += adjustments[1];
         self.width += adjustments[2];
         self.height += adjustments[3];
-        for i in 4..Self::PARAM_DIMENSION {
-            self.parameters[i - 4] += adjustments[i];
+        for (parameter, adjustment) in self.parameters.iter_mut().zip(&adjustments[4..]) {
+            *parameter += adjustment;
         }
     }
+
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        if S::VARIABLES < Self::PARAM_DIMENSION {
+            return finite_difference_jacobian(self, input, epsilon);
+        }
+        let parameters = self
+            .all_parameters()
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| S::variable(value, i))
+            .collect::<Vec<_>>();
+        let output = self.evaluate(&parameters, &[input[0]]);
+        array::from_fn(|_| array::from_fn(|i| output.derivative(i)))
+    }
 }
 
+/// `function` may be written over any [`Scalar`] `S`; choosing `S = Dual<P>` gives the
+/// equation an exact [`GradientDescent::jacobian`]
 #[derive(Debug)]
-pub struct ParametricEquation<F, const P: usize> {
+pub struct ParametricEquation<F, const P: usize, S = f64> {
     pub parameters: [f64; P],
     pub function: F,
+    scalar: PhantomData<S>,
 }
 
-impl<F, const P: usize> ParametricEquation<F, P> {
+impl<F, const P: usize, S> ParametricEquation<F, P, S> {
     pub fn new(function: F) -> Self {
         Self {
             parameters: [0.0; P],
             function,
+            scalar: PhantomData,
         }
     }
 }
 
-impl<F, const P: usize> Default for ParametricEquation<F, P>
+impl<F, const P: usize, S> Default for ParametricEquation<F, P, S>
 where
     F: Default,
 {
@@ -381,7 +590,7 @@ where
     }
 }
 
-impl<F, const P: usize> Display for ParametricEquation<F, P> {
+impl<F, const P: usize, S> Display for ParametricEquation<F, P, S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self { parameters, .. } = self;
         let parameters = parameters
@@ -393,9 +602,10 @@ impl<F, const P: usize> Display for ParametricEquation<F, P> {
     }
 }
 
-impl<F, const P: usize> GradientDescent for ParametricEquation<F, P>
+impl<F, const P: usize, S> GradientDescent for ParametricEquation<F, P, S>
 where
-    F: Fn(f64, [f64; P]) -> f64,
+    F: Fn(S, [S; P]) -> S,
+    S: Scalar,
 {
     const IN_DIMENSION: usize = 1;
 
@@ -403,22 +613,147 @@ where
 
     const OUT_DIMENSION: usize = 1;
 
-    fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64; 1]) -> [f64; 1] {
-        let x = input[0];
-        let output = match nudge {
-            None => (self.function)(x, self.parameters),
-            Some((n, epsilon)) => {
-                let mut new_parameters = self.parameters.clone();
-                new_parameters[n] += epsilon;
-                (self.function)(x, new_parameters)
-            }
-        };
-        [output]
+    fn predict(
+        &self,
+        nudge: Option<(usize, f64)>,
+        input: &[f64; Self::IN_DIMENSION],
+    ) -> [f64; Self::OUT_DIMENSION] {
+        let parameters = nudged(self.parameters, nudge);
+        let output = (self.function)(S::constant(input[0]), parameters.map(S::constant));
+        array::from_fn(|_| output.value())
+    }
+
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION] {
+        array::from_fn(|i| self.parameters[i])
+    }
+
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]) {
+        self.parameters = array::from_fn(|i| parameters[i]);
+    }
+
+    fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]) {
+        for (parameter, adjustment) in self.parameters.iter_mut().zip(adjustments) {
+            *parameter += adjustment;
+        }
+    }
+
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        if S::VARIABLES < Self::PARAM_DIMENSION {
+            return finite_difference_jacobian(self, input, epsilon);
+        }
+        let parameters = array::from_fn(|i| S::variable(self.parameters[i], i));
+        let output = (self.function)(S::constant(input[0]), parameters);
+        array::from_fn(|_| array::from_fn(|i| output.derivative(i)))
+    }
+}
+
+/// One or more [expressions](Expression) sharing their parameters, each giving one output
+#[derive(Clone, Debug)]
+pub struct Formula<const P: usize, const OUTPUTS: usize = 1> {
+    pub parameters: [f64; P],
+    pub expressions: [Expression; OUTPUTS],
+}
+
+impl<const P: usize, const OUTPUTS: usize> Display for Formula<P, OUTPUTS> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_formula(f, &self.expressions, &self.parameters)
+    }
+}
+
+impl<const P: usize, const OUTPUTS: usize> GradientDescent for Formula<P, OUTPUTS> {
+    const IN_DIMENSION: usize = 1;
+
+    const PARAM_DIMENSION: usize = P;
+
+    const OUT_DIMENSION: usize = OUTPUTS;
+
+    fn predict(
+        &self,
+        nudge: Option<(usize, f64)>,
+        input: &[f64; Self::IN_DIMENSION],
+    ) -> [f64; Self::OUT_DIMENSION] {
+        let parameters = nudged(self.parameters, nudge);
+        array::from_fn(|i| self.expressions[i].evaluate(input[0], &parameters))
+    }
+
+    fn parameters(&self) -> [f64; Self::PARAM_DIMENSION] {
+        array::from_fn(|i| self.parameters[i])
+    }
+
+    fn set_parameters(&mut self, parameters: [f64; Self::PARAM_DIMENSION]) {
+        self.parameters = array::from_fn(|i| parameters[i]);
+    }
+
+    fn parameter_names(&self) -> [String; Self::PARAM_DIMENSION] {
+        let names = self.expressions[0].parameters();
+        array::from_fn(|i| names[i].clone())
     }
 
     fn descend(&mut self, adjustments: [f64; Self::PARAM_DIMENSION]) {
-        for i in 0..Self::PARAM_DIMENSION {
-            self.parameters[i] += adjustments[i];
+        for (parameter, adjustment) in self.parameters.iter_mut().zip(adjustments) {
+            *parameter += adjustment;
         }
     }
+
+    fn jacobian(
+        &self,
+        input: &[f64; Self::IN_DIMENSION],
+        _epsilon: f64,
+    ) -> [[f64; Self::PARAM_DIMENSION]; Self::OUT_DIMENSION] {
+        let parameters = Dual::<P>::variables(self.parameters);
+        array::from_fn(|output| {
+            let output = self.expressions[output].evaluate(input[0], &parameters);
+            array::from_fn(|i| output.derivatives[i])
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exponential, Linear, MultiLinear, Polynomial};
+    use crate::{finite_difference_jacobian, GradientDescent};
+
+    /// Check the regressor's exact jacobian against finite differences at each input
+    fn assert_exact_jacobian<R>(regressor: &R, inputs: &[[f64; R::IN_DIMENSION]])
+    where
+        R: GradientDescent,
+        [(); R::IN_DIMENSION]:,
+        [(); R::PARAM_DIMENSION]:,
+        [(); R::OUT_DIMENSION]:,
+    {
+        let epsilon = 1e-7;
+        for input in inputs {
+            let exact = regressor.jacobian(input, epsilon);
+            let estimated = finite_difference_jacobian(regressor, input, epsilon);
+            for (exact, estimated) in exact.iter().flatten().zip(estimated.iter().flatten()) {
+                assert!(
+                    (exact - estimated).abs() <= 1e-5 * exact.abs().max(1.0),
+                    "{exact} != {estimated} at {input:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exact_jacobians() {
+        let inputs = [[-1.5], [0.5], [3.0]];
+        for (slope, y_intercept) in [(-1.5, 0.7), (2.0, -3.0)] {
+            assert_exact_jacobian(&Linear { slope, y_intercept }, &inputs);
+        }
+        for (base, growth) in [(2.5, 1.3), (-0.4, 0.8)] {
+            assert_exact_jacobian(&Exponential { base, growth }, &inputs);
+        }
+        for terms in [[0.5, -2.0, 0.25, 1.5], [-1.0, 0.0, 3.0, -0.125]] {
+            assert_exact_jacobian(&Polynomial { terms }, &inputs);
+        }
+        let multi_linear = MultiLinear {
+            weights: [1.5, -0.5, 2.0],
+            intercept: 0.3,
+        };
+        assert_exact_jacobian(&multi_linear, &[[1.0, -2.0, 0.5], [0.0, 4.0, -3.5]]);
+    }
 }
@@ -0,0 +1,303 @@
+//! Fitted models saved to JSON files, to be loaded back to continue the fit or to predict with
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    functions::Function,
+    json,
+    losses::Loss,
+    statistics::{Covariance, DynamicCovariance},
+};
+
+/// Which regressor a model is, with whatever besides its parameters is needed to rebuild it.
+/// Regressors of closures are rebuilt from the named [`Function`] they were made of
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ModelSpec {
+    Linear,
+    MultiLinear {
+        inputs: usize,
+    },
+    Exponential,
+    Polynomial {
+        degree: usize,
+    },
+    ScaledTranslated {
+        function: Function,
+    },
+    Parametric {
+        function: Function,
+    },
+    ParametricScaledTranslated {
+        function: Function,
+    },
+    /// Expressions separated by `;`, one for each output
+    Expression {
+        expression: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    pub value: f64,
+}
+
+/// How a saved model was fit
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FitMetadata {
+    /// File of the data the model was fit to, or - for standard input
+    pub data_file: PathBuf,
+    pub loss: Loss,
+    pub iterations: usize,
+    /// Mean loss at the end of the fit, unless it wasn't finite
+    pub error: Option<f64>,
+    pub converged: bool,
+    /// When the fit finished, in seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl FitMetadata {
+    /// Seconds since the Unix epoch, for `timestamp`
+    pub fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+/// [`Covariance`] of the parameters of a saved model, for the uncertainty of its predictions
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedCovariance {
+    pub matrix: Vec<Vec<f64>>,
+    pub residual_variance: f64,
+    pub degrees_of_freedom: f64,
+    /// Weight of a new observation of each output, for its prediction interval, unless the data
+    /// were weighted datum by datum and new observations have no known weight
+    #[serde(default)]
+    pub observation_weights: Option<Vec<f64>>,
+}
+
+impl SavedCovariance {
+    fn is_finite(&self) -> bool {
+        let values = self.matrix.iter().flatten();
+        values
+            .chain([&self.residual_variance])
+            .all(|x| x.is_finite())
+    }
+}
+
+impl<const N: usize> From<&Covariance<N>> for SavedCovariance {
+    fn from(covariance: &Covariance<N>) -> Self {
+        Self {
+            matrix: covariance.matrix.iter().map(|row| row.to_vec()).collect(),
+            residual_variance: covariance.residual_variance,
+            degrees_of_freedom: covariance.degrees_of_freedom,
+            observation_weights: None,
+        }
+    }
+}
+
+impl<const N: usize> TryFrom<&SavedCovariance> for Covariance<N> {
+    type Error = Box<dyn Error>;
+
+    fn try_from(saved: &SavedCovariance) -> Result<Self, Self::Error> {
+        if saved.matrix.len() != N || saved.matrix.iter().any(|row| row.len() != N) {
+            return Err(format!("the saved covariance is not a {N}x{N} matrix").into());
+        }
+        Ok(Self {
+            matrix: std::array::from_fn(|i| std::array::from_fn(|j| saved.matrix[i][j])),
+            residual_variance: saved.residual_variance,
+            degrees_of_freedom: saved.degrees_of_freedom,
+        })
+    }
+}
+
+impl From<&DynamicCovariance> for SavedCovariance {
+    fn from(covariance: &DynamicCovariance) -> Self {
+        Self {
+            matrix: covariance.matrix.clone(),
+            residual_variance: covariance.residual_variance,
+            degrees_of_freedom: covariance.degrees_of_freedom,
+            observation_weights: None,
+        }
+    }
+}
+
+impl From<&SavedCovariance> for DynamicCovariance {
+    fn from(saved: &SavedCovariance) -> Self {
+        Self {
+            matrix: saved.matrix.clone(),
+            residual_variance: saved.residual_variance,
+            degrees_of_freedom: saved.degrees_of_freedom,
+        }
+    }
+}
+
+/// Fitted model as saved to a file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedModel {
+    pub model: ModelSpec,
+    /// Every parameter of the model, in its order
+    pub parameters: Vec<Parameter>,
+    /// Covariance of the parameters, if they were identifiable from the data
+    #[serde(default)]
+    pub covariance: Option<SavedCovariance>,
+    pub fit: FitMetadata,
+}
+
+impl SavedModel {
+    /// Save the model to `path` as JSON, leaving out the covariance and error if they aren't
+    /// finite, which JSON can't represent. Fails if a parameter isn't finite
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parameter) = self.parameters.iter().find(|p| !p.value.is_finite()) {
+            return Err(format!(
+                "can't save a model with non-finite parameter {}",
+                parameter.name
+            )
+            .into());
+        }
+        let mut saved = self.clone();
+        saved.covariance = saved.covariance.filter(SavedCovariance::is_finite);
+        saved.fit.error = saved.fit.error.filter(|error| error.is_finite());
+        let mut json = json::to_string(&saved)?;
+        json.push('\n');
+        fs::write(path, json).map_err(|err| format!("can't write {}: {err}", path.display()))?;
+        Ok(())
+    }
+
+    /// Load a model saved by [`save`](Self::save)
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let json = fs::read_to_string(path)
+            .map_err(|err| format!("can't read {}: {err}", path.display()))?;
+        Ok(json::from_str(&json)
+            .map_err(|err| format!("invalid model file {}: {err}", path.display()))?)
+    }
+
+    /// Values of the parameters, for a regressor whose parameters are named `names`, checking
+    /// they are the ones saved
+    pub fn parameter_values(&self, names: &[String]) -> Result<Vec<f64>, Box<dyn Error>> {
+        let saved = self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.name.as_str())
+            .collect::<Vec<_>>();
+        if saved != names {
+            return Err(format!(
+                "the saved model has parameters {saved:?}, but the regressor has {names:?}"
+            )
+            .into());
+        }
+        Ok(self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.value)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::{FitMetadata, ModelSpec, Parameter, SavedCovariance, SavedModel};
+    use crate::{
+        dynamic::{Formula, Regressor},
+        functions::Function,
+        losses::Loss,
+        statistics::Covariance,
+    };
+
+    #[test]
+    fn saves_and_loads() {
+        let mut formula = Formula::parse("a * x + b; b - x").unwrap();
+        formula.set_parameters(&[2.0, -0.25]).unwrap();
+        let model = SavedModel {
+            model: ModelSpec::Expression {
+                expression: "a * x + b; b - x".into(),
+            },
+            parameters: formula
+                .parameter_names()
+                .into_iter()
+                .zip(formula.parameters())
+                .map(|(name, value)| Parameter { name, value })
+                .collect(),
+            covariance: None,
+            fit: FitMetadata {
+                data_file: "data.csv".into(),
+                loss: Loss::Huber { delta: 0.5 },
+                iterations: 12,
+                error: Some(1.5e-3),
+                converged: true,
+                timestamp: FitMetadata::now(),
+            },
+        };
+        let path = env::temp_dir().join(format!("saved-model-{}.json", std::process::id()));
+        model.save(&path).unwrap();
+        let loaded = SavedModel::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.model, model.model);
+        assert_eq!(loaded.parameters, model.parameters);
+        assert_eq!(loaded.fit.timestamp, model.fit.timestamp);
+        assert!(matches!(loaded.fit.loss, Loss::Huber { delta } if delta == 0.5));
+
+        let ModelSpec::Expression { expression } = &loaded.model else {
+            unreachable!()
+        };
+        let mut restored = Formula::parse(expression).unwrap();
+        let values = loaded
+            .parameter_values(&restored.parameter_names())
+            .unwrap();
+        restored.set_parameters(&values).unwrap();
+        assert_eq!(restored.predict(None, &[3.0]), [5.75, -3.25]);
+        assert!(loaded.parameter_values(&["b".into(), "a".into()]).is_err());
+
+        let covariance = Covariance::<2> {
+            matrix: [[0.5, -0.25], [-0.25, 2.0]],
+            residual_variance: 0.1,
+            degrees_of_freedom: 8.0,
+        };
+        let saved = SavedCovariance::from(&covariance);
+        let restored = Covariance::<2>::try_from(&saved).unwrap();
+        assert_eq!(restored.matrix, covariance.matrix);
+        assert!(Covariance::<3>::try_from(&saved).is_err());
+
+        // JSON has no NaN or infinity, so those are left out rather than failing to save
+        let mut undefined = model.clone();
+        undefined.covariance = Some(SavedCovariance {
+            residual_variance: f64::INFINITY,
+            ..saved
+        });
+        undefined.fit.error = Some(f64::NAN);
+        undefined.save(&path).unwrap();
+        let loaded = SavedModel::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.covariance.is_none());
+        assert_eq!(loaded.fit.error, None);
+        assert_eq!(loaded.parameters, model.parameters);
+
+        // but parameters can't be left out
+        let mut diverged = model.clone();
+        diverged.parameters[1].value = f64::INFINITY;
+        let error = diverged.save(&path).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "can't save a model with non-finite parameter b"
+        );
+        assert!(!path.exists());
+
+        let spec = ModelSpec::Parametric {
+            function: Function::PowerDecay,
+        };
+        let json = crate::json::to_string(&spec).unwrap();
+        assert!(json.contains("\"function\": \"power-decay\""));
+        assert_eq!(crate::json::from_str::<ModelSpec>(&json).unwrap(), spec);
+    }
+}
@@ -0,0 +1,363 @@
+#[cfg(feature = "nightly")]
+use std::array;
+
+use crate::linalg::solve_dynamic;
+#[cfg(feature = "nightly")]
+use crate::{
+    linalg::least_squares, residuals_and_jacobian, weighted_squared_error, GradientDescent, Record,
+};
+
+/// Levenberg–Marquardt weighted least squares: each step solves the damped Gauss–Newton equations
+/// `(JᵀWJ + λ diag(JᵀWJ)) δ = JᵀWr`, lowering the damping λ when a step reduces the error and
+/// raising it (falling back towards gradient descent) when it doesn't
+pub struct LevenbergMarquardt {
+    pub damping: f64,
+    /// Weight of the squared errors of each output
+    pub output_weights: Vec<f64>,
+    pub finite_differences: bool,
+    pub epsilon: f64,
+}
+
+impl LevenbergMarquardt {
+    /// Attempts allowed per step before giving up on finding a step that reduces the error
+    const ATTEMPTS: usize = 16;
+    const MIN_DAMPING: f64 = 1e-15;
+    const MAX_DAMPING: f64 = 1e15;
+
+    /// Take one step, returning the weighted sum of squared errors and its downhill gradient from
+    /// before the step, and whether a step reducing the error was found
+    #[cfg(feature = "nightly")]
+    pub fn step<R>(
+        &mut self,
+        regressor: &mut R,
+        data: &[Record<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>],
+    ) -> (f64, [f64; R::PARAM_DIMENSION], bool)
+    where
+        R: GradientDescent,
+        [(); R::IN_DIMENSION]:,
+        [(); R::PARAM_DIMENSION]:,
+        [(); R::OUT_DIMENSION]:,
+    {
+        let (residuals, jacobian) = residuals_and_jacobian(
+            regressor,
+            data,
+            &self.output_weights,
+            self.finite_differences,
+            self.epsilon,
+        );
+        let (error, gradient, progressed) = Self::damped_step(
+            &mut self.damping,
+            regressor,
+            R::PARAM_DIMENSION,
+            &residuals,
+            &jacobian,
+            |regressor, step| regressor.descend(array::from_fn(|i| step[i])),
+            |regressor| sum_squared_error(regressor, data, &self.output_weights),
+        );
+        (error, array::from_fn(|i| gradient[i]), progressed)
+    }
+
+    /// [`step`](Self::step) for a model of any size, from its weighted `residuals` and the rows of
+    /// their `jacobian` over its `parameters`. `descend` adjusts the model's parameters, and
+    /// `squared_error` gives the weighted sum of squared errors of its current ones
+    pub(crate) fn damped_step<M, Row>(
+        damping: &mut f64,
+        model: &mut M,
+        parameters: usize,
+        residuals: &[f64],
+        jacobian: &[Row],
+        descend: impl Fn(&mut M, &[f64]),
+        squared_error: impl Fn(&M) -> f64,
+    ) -> (f64, Vec<f64>, bool)
+    where
+        M: ?Sized,
+        Row: AsRef<[f64]>,
+    {
+        let mut error = 0.0;
+        let mut normal = vec![vec![0.0; parameters]; parameters];
+        let mut gradient = vec![0.0; parameters];
+        for (residual, row) in residuals.iter().zip(jacobian) {
+            let row = row.as_ref();
+            error += residual * residual;
+            for i in 0..parameters {
+                gradient[i] += row[i] * residual;
+                for j in 0..parameters {
+                    normal[i][j] += row[i] * row[j];
+                }
+            }
+        }
+
+        let mut progressed = false;
+        for _ in 0..Self::ATTEMPTS {
+            let mut damped = normal.clone();
+            for (i, row) in damped.iter_mut().enumerate() {
+                row[i] += *damping * normal[i][i].max(f64::MIN_POSITIVE);
+            }
+            let step = solve_dynamic(damped, gradient.clone())
+                .filter(|step| step.iter().all(|x| x.is_finite()));
+            if let Some(step) = step {
+                descend(model, &step);
+                if squared_error(model) < error {
+                    *damping = (*damping / 10.0).max(Self::MIN_DAMPING);
+                    progressed = true;
+                    break;
+                }
+                descend(model, &step.iter().map(|x| -x).collect::<Vec<_>>());
+            }
+            if *damping >= Self::MAX_DAMPING {
+                break;
+            }
+            *damping = (*damping * 10.0).min(Self::MAX_DAMPING);
+        }
+        (
+            error,
+            gradient.iter().map(|x| 2.0 * x).collect(),
+            progressed,
+        )
+    }
+}
+
+#[cfg(feature = "nightly")]
+/// Fit a regressor that is [linear in its parameters](GradientDescent::LINEAR) exactly, in a
+/// single weighted least squares solve for the adjustment to its current parameters, with its
+/// derivatives estimated by finite differences of step `epsilon` if it doesn't know them exactly
+/// or `finite_differences` is set. Returns whether the problem had a unique solution
+pub fn linear_least_squares<R>(
+    regressor: &mut R,
+    data: &[Record<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>],
+    output_weights: &[f64],
+    finite_differences: bool,
+    epsilon: f64,
+) -> bool
+where
+    R: GradientDescent,
+    [(); R::IN_DIMENSION]:,
+    [(); R::PARAM_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    // each equation is scaled by the square root of its weight, which weights its squared error
+    let (residuals, rows) =
+        residuals_and_jacobian(regressor, data, output_weights, finite_differences, epsilon);
+    match least_squares(rows, residuals) {
+        Some(adjustments) => {
+            regressor.descend(adjustments);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(feature = "nightly")]
+fn sum_squared_error<R>(
+    regressor: &R,
+    data: &[Record<{ R::IN_DIMENSION }, { R::OUT_DIMENSION }>],
+    output_weights: &[f64],
+) -> f64
+where
+    R: GradientDescent,
+    [(); R::IN_DIMENSION]:,
+    [(); R::OUT_DIMENSION]:,
+{
+    data.iter()
+        .map(|datum| {
+            let prediction = regressor.predict(None, &datum.x);
+            weighted_squared_error(&datum.y, &datum.weight, output_weights, &prediction)
+        })
+        .sum()
+}
+
+#[cfg(all(test, feature = "nightly"))]
+mod tests {
+    use super::{linear_least_squares, LevenbergMarquardt};
+    use crate::{
+        expression::Expression,
+        regressors::{Exponential, Formula, MultiLinear, Polynomial},
+        GradientDescent, Record,
+    };
+
+    /// Linear in its parameter, but leaving its derivatives to finite differences
+    struct Proportional {
+        gain: f64,
+    }
+
+    impl GradientDescent for Proportional {
+        const IN_DIMENSION: usize = 1;
+        const PARAM_DIMENSION: usize = 1;
+        const OUT_DIMENSION: usize = 1;
+        const LINEAR: bool = true;
+
+        fn predict(&self, nudge: Option<(usize, f64)>, input: &[f64; 1]) -> [f64; 1] {
+            let gain = self.gain + nudge.map_or(0.0, |(_, epsilon)| epsilon);
+            [gain * input[0]]
+        }
+
+        fn parameters(&self) -> [f64; 1] {
+            [self.gain]
+        }
+
+        fn set_parameters(&mut self, [gain]: [f64; 1]) {
+            self.gain = gain;
+        }
+
+        fn descend(&mut self, [adjustment]: [f64; 1]) {
+            self.gain += adjustment;
+        }
+    }
+
+    #[test]
+    fn fits_exponential() {
+        let data = (0..20)
+            .map(|i| {
+                let x = i as f64 / 4.0;
+                Record {
+                    x: [x],
+                    y: [2.5 * 1.3f64.powf(x)],
+                    weight: [1.0],
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut regressor = Exponential::default();
+        let mut solver = LevenbergMarquardt {
+            damping: 1e-3,
+            output_weights: vec![1.0],
+            finite_differences: false,
+            epsilon: 1e-8,
+        };
+        while solver.step(&mut regressor, &data).2 {}
+        assert!((regressor.base - 2.5).abs() < 1e-9);
+        assert!((regressor.growth - 1.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fits_polynomial() {
+        let data = (0..20)
+            .map(|i| {
+                let x = i as f64 - 10.0;
+                Record {
+                    x: [x],
+                    y: [1.5 - 2.0 * x + 0.25 * x * x],
+                    weight: [1.0],
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut regressor = Polynomial::<3>::default();
+        assert!(linear_least_squares(
+            &mut regressor,
+            &data,
+            &[1.0],
+            false,
+            1e-8
+        ));
+        for (term, expected) in regressor.terms.into_iter().zip([1.5, -2.0, 0.25]) {
+            assert!((term - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn fits_multilinear() {
+        let data = (0..20)
+            .map(|i| {
+                let x = [i as f64, (i * i % 7) as f64];
+                Record {
+                    x,
+                    y: [0.5 * x[0] - 2.0 * x[1] + 3.0],
+                    weight: [1.0],
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut regressor = MultiLinear::<2>::default();
+        assert!(linear_least_squares(
+            &mut regressor,
+            &data,
+            &[1.0],
+            false,
+            1e-8
+        ));
+        for (weight, expected) in regressor.weights.into_iter().zip([0.5, -2.0]) {
+            assert!((weight - expected).abs() < 1e-9);
+        }
+        assert!((regressor.intercept - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fits_outputs_together() {
+        // both coordinates of a thrown ball, sharing the time of flight
+        let data = (0..20)
+            .map(|i| {
+                let t = i as f64 / 10.0;
+                Record {
+                    x: [t],
+                    y: [2.4 * t, 1.0 + 3.2 * t - 4.9 * t * t],
+                    weight: [1.0, 1.0],
+                }
+            })
+            .collect::<Vec<_>>();
+        let expressions = Expression::parse_system("v * x; h + w * x - g / 2 * x ^ 2").unwrap();
+        let mut regressor = Formula::<4, 2> {
+            parameters: [1.0; 4],
+            expressions: expressions.try_into().unwrap(),
+        };
+        let mut solver = LevenbergMarquardt {
+            damping: 1e-3,
+            output_weights: vec![1.0, 0.5],
+            finite_differences: false,
+            epsilon: 1e-8,
+        };
+        for _ in 0..100 {
+            if !solver.step(&mut regressor, &data).2 {
+                break;
+            }
+        }
+        for (parameter, expected) in regressor.parameters.into_iter().zip([2.4, 1.0, 3.2, 9.8]) {
+            assert!((parameter - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn fits_linear_by_finite_differences() {
+        let data = (1..10)
+            .map(|i| Record {
+                x: [i as f64],
+                y: [2.5 * i as f64],
+                weight: [1.0],
+            })
+            .collect::<Vec<_>>();
+        let mut regressor = Proportional { gain: 0.0 };
+        assert!(linear_least_squares(
+            &mut regressor,
+            &data,
+            &[1.0],
+            false,
+            1e-6
+        ));
+        assert!((regressor.gain - 2.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn weights_data() {
+        // an outlier with no weight has no effect on the fit
+        let mut data = (0..10)
+            .map(|i| Record {
+                x: [i as f64],
+                y: [3.0 * i as f64 - 1.0],
+                weight: [2.0],
+            })
+            .collect::<Vec<_>>();
+        data.push(Record {
+            x: [4.5],
+            y: [100.0],
+            weight: [0.0],
+        });
+        let mut regressor = Polynomial::<2>::default();
+        assert!(linear_least_squares(
+            &mut regressor,
+            &data,
+            &[1.0],
+            false,
+            1e-8
+        ));
+        for (term, expected) in regressor.terms.into_iter().zip([-1.0, 3.0]) {
+            assert!((term - expected).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,634 @@
+use std::fmt::Display;
+
+use crate::linalg::{invert, invert_dynamic};
+
+/// Summary of how well a fitted model describes the data it was fitted to
+#[derive(Clone, Debug)]
+pub struct FitReport {
+    pub observations: usize,
+    pub parameters: usize,
+    pub degrees_of_freedom: usize,
+    pub r_squared: f64,
+    pub adjusted_r_squared: f64,
+    pub rmse: f64,
+    pub mae: f64,
+    /// Weighted sum of squared residuals
+    pub chi_squared: f64,
+    /// χ² per degree of freedom; near 1 when the weights are the inverse variances of the data
+    /// and the model fits it
+    pub reduced_chi_squared: f64,
+    /// Akaike information criterion, assuming normally distributed residuals
+    pub aic: f64,
+    /// Bayesian information criterion, assuming normally distributed residuals
+    pub bic: f64,
+    pub residual_mean: f64,
+    pub residual_std: f64,
+    /// Durbin-Watson statistic of the residuals in the order of the data; near 2 when
+    /// consecutive residuals are uncorrelated
+    pub durbin_watson: f64,
+}
+
+impl FitReport {
+    /// Compare the `observed` values to the model's `predicted` values for the same inputs, where
+    /// the model has `parameters` free parameters. Each residual counts as much as its weight;
+    /// with weights of `1/σ²` the weighted sum of squared residuals is the χ² statistic
+    pub fn new(observed: &[f64], predicted: &[f64], weights: &[f64], parameters: usize) -> Self {
+        let n = observed.len() as f64;
+        let k = parameters as f64;
+        let total_weight = weights.iter().sum::<f64>();
+        let weighted_mean = |values: &[f64]| {
+            values.iter().zip(weights).map(|(v, w)| w * v).sum::<f64>() / total_weight
+        };
+        let residuals = observed
+            .iter()
+            .zip(predicted)
+            .map(|(y, prediction)| y - prediction)
+            .collect::<Vec<_>>();
+        let observed_mean = weighted_mean(observed);
+        let total_squares = observed
+            .iter()
+            .zip(weights)
+            .map(|(y, w)| w * (y - observed_mean) * (y - observed_mean))
+            .sum::<f64>();
+        let chi_squared = residuals
+            .iter()
+            .zip(weights)
+            .map(|(r, w)| w * r * r)
+            .sum::<f64>();
+        let residual_mean = weighted_mean(&residuals);
+        let residual_variance = residuals
+            .iter()
+            .zip(weights)
+            .map(|(r, w)| w * (r - residual_mean) * (r - residual_mean))
+            .sum::<f64>()
+            / total_weight
+            * n
+            / (n - 1.0);
+        // residuals scaled to unit weight, so that those of uncertain data don't dominate
+        let scaled = residuals
+            .iter()
+            .zip(weights)
+            .map(|(r, w)| r * w.sqrt())
+            .collect::<Vec<_>>();
+        let successive_squares = scaled
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]) * (pair[1] - pair[0]))
+            .sum::<f64>();
+        let r_squared = 1.0 - chi_squared / total_squares;
+        let log_likelihood_term = n * (chi_squared / n).ln();
+
+        Self {
+            observations: observed.len(),
+            parameters,
+            degrees_of_freedom: observed.len().saturating_sub(parameters),
+            r_squared,
+            adjusted_r_squared: 1.0 - (1.0 - r_squared) * (n - 1.0) / (n - k),
+            rmse: (chi_squared / total_weight).sqrt(),
+            mae: weighted_mean(&residuals.iter().map(|r| r.abs()).collect::<Vec<_>>()),
+            chi_squared,
+            reduced_chi_squared: chi_squared / (n - k),
+            aic: log_likelihood_term + 2.0 * k,
+            bic: log_likelihood_term + k * n.ln(),
+            residual_mean,
+            residual_std: residual_variance.sqrt(),
+            durbin_watson: successive_squares / chi_squared,
+        }
+    }
+}
+
+impl Display for FitReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "observations: {}, parameters: {}, degrees of freedom: {}",
+            self.observations, self.parameters, self.degrees_of_freedom
+        )?;
+        writeln!(
+            f,
+            "R²: {}, adjusted R²: {}",
+            self.r_squared, self.adjusted_r_squared
+        )?;
+        writeln!(f, "RMSE: {}, MAE: {}", self.rmse, self.mae)?;
+        writeln!(
+            f,
+            "χ²: {}, reduced χ²: {}",
+            self.chi_squared, self.reduced_chi_squared
+        )?;
+        writeln!(f, "AIC: {}, BIC: {}", self.aic, self.bic)?;
+        write!(
+            f,
+            "residuals: mean {}, std {}, Durbin-Watson {}",
+            self.residual_mean, self.residual_std, self.durbin_watson
+        )
+    }
+}
+
+/// A fitted parameter along with its uncertainty
+#[derive(Clone, Debug)]
+pub struct ParameterEstimate {
+    pub value: f64,
+    pub standard_error: f64,
+    pub t_statistic: f64,
+    /// Two-sided probability of a t statistic at least this large if the parameter were zero
+    pub p_value: f64,
+    pub confidence_interval: (f64, f64),
+}
+
+impl Display for ParameterEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ± {} (t: {}, p: {}, interval: [{}, {}])",
+            self.value,
+            self.standard_error,
+            self.t_statistic,
+            self.p_value,
+            self.confidence_interval.0,
+            self.confidence_interval.1
+        )
+    }
+}
+
+/// Covariance of least squares parameter estimates, `s² (JᵀJ)⁻¹`, linearized around the fitted
+/// parameters from the jacobian `J` of the model at each datum, where `s²` is the residual
+/// variance. For weighted least squares, scale each residual and row of the jacobian by the
+/// square root of its weight
+#[derive(Clone, Debug)]
+pub struct Covariance<const N: usize> {
+    pub matrix: [[f64; N]; N],
+    pub residual_variance: f64,
+    pub degrees_of_freedom: f64,
+}
+
+impl<const N: usize> Covariance<N> {
+    /// Returns `None` if the parameters aren't identifiable from the data, which makes `JᵀJ`
+    /// singular, or if there are no more residuals than parameters to estimate the residual
+    /// variance from
+    pub fn new(jacobian: &[[f64; N]], residuals: &[f64]) -> Option<Self> {
+        let mut normal = [[0.0; N]; N];
+        add_normal_matrix(jacobian, &mut normal);
+        let inverse = invert(normal)?;
+        let (residual_variance, degrees_of_freedom) = residual_variance(residuals, N)?;
+        let matrix = inverse.map(|row| row.map(|value| value * residual_variance));
+        matrix
+            .iter()
+            .flatten()
+            .all(|x| x.is_finite())
+            .then_some(Self {
+                matrix,
+                residual_variance,
+                degrees_of_freedom,
+            })
+    }
+
+    /// Half width of the interval that covers the true value with probability `confidence`,
+    /// for a statistic with the given standard error
+    pub fn margin(&self, standard_error: f64, confidence: f64) -> f64 {
+        margin(standard_error, confidence, self.degrees_of_freedom)
+    }
+
+    /// Uncertainty of each of the fitted parameter `values`, with intervals covering the true
+    /// values with probability `confidence`
+    pub fn estimates(&self, values: [f64; N], confidence: f64) -> [ParameterEstimate; N] {
+        std::array::from_fn(|i| {
+            estimate(
+                values[i],
+                self.matrix[i][i],
+                confidence,
+                self.degrees_of_freedom,
+            )
+        })
+    }
+
+    /// Standard error of the model's mean prediction at a point where its derivatives with
+    /// respect to the parameters are `gradient`, and of a new observation made there with the
+    /// given `weight`. The residual variance is that of an observation of weight 1, so for data
+    /// weighted by `1 / σ²` a new observation of error `σ` has variance `σ² s²`
+    pub fn prediction_errors(&self, gradient: [f64; N], weight: f64) -> (f64, f64) {
+        prediction_errors(&self.matrix, &gradient, self.residual_variance / weight)
+    }
+}
+
+/// [`Covariance`] of a model whose number of parameters is only known at runtime
+#[derive(Clone, Debug)]
+pub struct DynamicCovariance {
+    pub matrix: Vec<Vec<f64>>,
+    pub residual_variance: f64,
+    pub degrees_of_freedom: f64,
+}
+
+impl DynamicCovariance {
+    /// [`Covariance::new`] for a jacobian whose rows each have the derivatives with respect to
+    /// the `parameters`
+    pub fn new<Row: AsRef<[f64]>>(
+        jacobian: &[Row],
+        residuals: &[f64],
+        parameters: usize,
+    ) -> Option<Self> {
+        let mut normal = vec![vec![0.0; parameters]; parameters];
+        add_normal_matrix(jacobian, &mut normal);
+        let inverse = invert_dynamic(&normal)?;
+        let (residual_variance, degrees_of_freedom) = residual_variance(residuals, parameters)?;
+        let matrix = inverse
+            .into_iter()
+            .map(|row| row.into_iter().map(|value| value * residual_variance))
+            .map(Iterator::collect::<Vec<_>>)
+            .collect::<Vec<_>>();
+        matrix
+            .iter()
+            .flatten()
+            .all(|x| x.is_finite())
+            .then_some(Self {
+                matrix,
+                residual_variance,
+                degrees_of_freedom,
+            })
+    }
+
+    /// [`Covariance::margin`]
+    pub fn margin(&self, standard_error: f64, confidence: f64) -> f64 {
+        margin(standard_error, confidence, self.degrees_of_freedom)
+    }
+
+    /// [`Covariance::estimates`]
+    pub fn estimates(&self, values: &[f64], confidence: f64) -> Vec<ParameterEstimate> {
+        values
+            .iter()
+            .zip(&self.matrix)
+            .enumerate()
+            .map(|(i, (value, row))| estimate(*value, row[i], confidence, self.degrees_of_freedom))
+            .collect()
+    }
+
+    /// [`Covariance::prediction_errors`]
+    pub fn prediction_errors(&self, gradient: &[f64], weight: f64) -> (f64, f64) {
+        prediction_errors(&self.matrix, gradient, self.residual_variance / weight)
+    }
+}
+
+/// Add `JᵀJ` to `normal`
+fn add_normal_matrix<Row: AsRef<[f64]>, Normal: AsMut<[f64]>>(
+    jacobian: &[Row],
+    normal: &mut [Normal],
+) {
+    for row in jacobian {
+        let row = row.as_ref();
+        for (normal_row, a) in normal.iter_mut().zip(row) {
+            for (value, b) in normal_row.as_mut().iter_mut().zip(row) {
+                *value += a * b;
+            }
+        }
+    }
+}
+
+/// Variance of the residuals left by fitting the given number of parameters, with its degrees
+/// of freedom, unless there are too few residuals or they aren't finite
+fn residual_variance(residuals: &[f64], parameters: usize) -> Option<(f64, f64)> {
+    let degrees_of_freedom = residuals.len() as f64 - parameters as f64;
+    if degrees_of_freedom <= 0.0 {
+        return None;
+    }
+    let residual_variance = residuals.iter().map(|r| r * r).sum::<f64>() / degrees_of_freedom;
+    residual_variance
+        .is_finite()
+        .then_some((residual_variance, degrees_of_freedom))
+}
+
+fn margin(standard_error: f64, confidence: f64, degrees_of_freedom: f64) -> f64 {
+    student_t_quantile(0.5 + confidence / 2.0, degrees_of_freedom) * standard_error
+}
+
+/// Estimate of a parameter of the given value and variance
+fn estimate(
+    value: f64,
+    variance: f64,
+    confidence: f64,
+    degrees_of_freedom: f64,
+) -> ParameterEstimate {
+    let standard_error = variance.sqrt();
+    let t_statistic = value / standard_error;
+    let margin = margin(standard_error, confidence, degrees_of_freedom);
+    ParameterEstimate {
+        value,
+        standard_error,
+        t_statistic,
+        p_value: student_t_p_value(t_statistic, degrees_of_freedom),
+        confidence_interval: (value - margin, value + margin),
+    }
+}
+
+/// Standard errors of the mean prediction and of a new observation with the given variance,
+/// at a point of the given gradient
+fn prediction_errors<Row: AsRef<[f64]>>(
+    matrix: &[Row],
+    gradient: &[f64],
+    observation_variance: f64,
+) -> (f64, f64) {
+    let variance = matrix
+        .iter()
+        .zip(gradient)
+        .map(|(row, a)| {
+            a * row
+                .as_ref()
+                .iter()
+                .zip(gradient)
+                .map(|(c, b)| c * b)
+                .sum::<f64>()
+        })
+        .sum::<f64>();
+    (variance.sqrt(), (variance + observation_variance).sqrt())
+}
+
+/// Two-sided tail probability of Student's t distribution beyond `t`
+pub fn student_t_p_value(t: f64, degrees_of_freedom: f64) -> f64 {
+    if t.is_infinite() {
+        return 0.0;
+    }
+    incomplete_beta(
+        degrees_of_freedom / 2.0,
+        0.5,
+        degrees_of_freedom / (degrees_of_freedom + t * t),
+    )
+}
+
+/// Value below which Student's t distribution has probability `probability`, for probabilities
+/// of at least one half
+pub fn student_t_quantile(probability: f64, degrees_of_freedom: f64) -> f64 {
+    let tail = 2.0 * (1.0 - probability);
+    if !(0.0..1.0).contains(&tail) || degrees_of_freedom <= 0.0 {
+        return f64::NAN;
+    }
+    let mut high = 1.0;
+    while student_t_p_value(high, degrees_of_freedom) > tail {
+        high *= 2.0;
+    }
+    let mut low = 0.0;
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if student_t_p_value(middle, degrees_of_freedom) > tail {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Value below which the standard normal distribution has probability `probability`, by Acklam's
+/// rational approximation
+pub fn normal_quantile(probability: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    let polynomial = |coefficients: &[f64], x: f64| {
+        coefficients
+            .iter()
+            .fold(0.0, |sum, coefficient| sum * x + coefficient)
+    };
+    // the tails use a different approximation than the central region
+    let tail = |p: f64| {
+        let q = (-2.0 * p.ln()).sqrt();
+        polynomial(&C, q) / (polynomial(&D, q) * q + 1.0)
+    };
+    match probability {
+        p if p <= 0.0 => f64::NEG_INFINITY,
+        p if p >= 1.0 => f64::INFINITY,
+        p if p < 0.024_25 => tail(p),
+        p if p > 1.0 - 0.024_25 => -tail(1.0 - p),
+        p => {
+            let q = p - 0.5;
+            let r = q * q;
+            polynomial(&A, r) * q / (polynomial(&B, r) * r + 1.0)
+        }
+    }
+}
+
+/// Natural logarithm of the gamma function, by the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    let t = x + 7.5;
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// Regularized incomplete beta function `I_x(a, b)`
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // the continued fraction converges quickly only below the mean, so use the symmetry
+    // I_x(a, b) = 1 - I_(1-x)(b, a) above it
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Continued fraction for the incomplete beta function, by the modified Lentz method
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let clamp = |value: f64| if value.abs() < TINY { TINY } else { value };
+    let mut c = 1.0;
+    let mut d = 1.0 / clamp(1.0 - (a + b) * x / (a + 1.0));
+    let mut fraction = d;
+    for m in 1..300 {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / clamp(1.0 + even * d);
+        c = clamp(1.0 + even / c);
+        fraction *= d * c;
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / clamp(1.0 + odd * d);
+        c = clamp(1.0 + odd / c);
+        let change = d * c;
+        fraction *= change;
+        if (change - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        normal_quantile, student_t_p_value, student_t_quantile, Covariance, DynamicCovariance,
+        FitReport,
+    };
+
+    #[test]
+    fn report() {
+        let observed = [1.0, 3.0, 2.0, 5.0, 4.0];
+        let predicted = [1.5, 2.5, 2.5, 4.5, 4.5];
+        let report = FitReport::new(&observed, &predicted, &[1.0; 5], 2);
+        assert_eq!(report.degrees_of_freedom, 3);
+        // residuals alternate ±0.5 and the observed values have a total sum of squares of 10
+        assert!((report.r_squared - (1.0 - 1.25 / 10.0)).abs() < 1e-12);
+        assert!((report.adjusted_r_squared - (1.0 - 0.125 * 4.0 / 3.0)).abs() < 1e-12);
+        assert!((report.rmse - 0.5).abs() < 1e-12);
+        assert!((report.mae - 0.5).abs() < 1e-12);
+        assert!((report.residual_mean + 0.1).abs() < 1e-12);
+        // residuals -0.5, 0.5, -0.5, 0.5, -0.5 differ by 1 between every pair
+        assert!((report.durbin_watson - 4.0 / 1.25).abs() < 1e-12);
+        assert!((report.bic - report.aic - 2.0 * (5f64.ln() - 2.0)).abs() < 1e-12);
+        assert!((report.reduced_chi_squared - 1.25 / 3.0).abs() < 1e-12);
+
+        // weighting an observation is the same as repeating it, apart from the number of them
+        let doubled = FitReport::new(&observed, &predicted, &[1.0, 1.0, 1.0, 1.0, 2.0], 2);
+        let repeated = FitReport::new(
+            &[1.0, 3.0, 2.0, 5.0, 4.0, 4.0],
+            &[1.5, 2.5, 2.5, 4.5, 4.5, 4.5],
+            &[1.0; 6],
+            2,
+        );
+        assert!((doubled.r_squared - repeated.r_squared).abs() < 1e-12);
+        assert!((doubled.chi_squared - repeated.chi_squared).abs() < 1e-12);
+        assert!((doubled.residual_mean - repeated.residual_mean).abs() < 1e-12);
+    }
+
+    #[test]
+    fn student_t() {
+        assert!((student_t_quantile(0.975, 10.0) - 2.228_138_851_986_274).abs() < 1e-9);
+        assert!((student_t_quantile(0.95, 1.0) - 6.313_751_514_675_043).abs() < 1e-9);
+        assert!((student_t_p_value(2.228_138_851_986_274, 10.0) - 0.05).abs() < 1e-12);
+        assert!((student_t_p_value(0.0, 3.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn normal() {
+        assert_eq!(normal_quantile(0.5), 0.0);
+        assert!((normal_quantile(0.975) - 1.959_963_984_540_054).abs() < 1e-8);
+        assert!((normal_quantile(0.001) + 3.090_232_306_167_813_5).abs() < 1e-8);
+    }
+
+    #[test]
+    fn linear_estimates() {
+        // y = 0.8 x + 1.4 is the least squares line through these points
+        let data = [(0.0, 1.0), (1.0, 3.0), (2.0, 2.0), (3.0, 5.0), (4.0, 4.0)];
+        let jacobian = data.map(|(x, _)| [x, 1.0]);
+        let residuals = data.map(|(x, y)| y - (0.8 * x + 1.4));
+        let covariance = Covariance::new(&jacobian, &residuals).unwrap();
+        let [slope, intercept] = covariance.estimates([0.8, 1.4], 0.95);
+        assert!((slope.standard_error - 0.12f64.sqrt()).abs() < 1e-12);
+        assert!((intercept.standard_error - 0.72f64.sqrt()).abs() < 1e-12);
+        let half_width = slope.confidence_interval.1 - slope.value;
+        assert!((half_width - 3.182_446_305_284_263 * slope.standard_error).abs() < 1e-9);
+        // at x = 0 the mean prediction is the intercept
+        let (mean_error, prediction_error) = covariance.prediction_errors([0.0, 1.0], 1.0);
+        assert!((mean_error - intercept.standard_error).abs() < 1e-12);
+        assert!((prediction_error - (0.72f64 + 1.2).sqrt()).abs() < 1e-12);
+        assert!(Covariance::new(&[[1.0, 2.0]; 3], &[0.0; 3]).is_none());
+
+        // the same from a jacobian only sized at runtime
+        let dynamic = DynamicCovariance::new(&jacobian, &residuals, 2).unwrap();
+        let [dynamic_slope, dynamic_intercept] = &dynamic.estimates(&[0.8, 1.4], 0.95)[..] else {
+            panic!("expected two estimates");
+        };
+        assert_eq!(dynamic_slope.confidence_interval, slope.confidence_interval);
+        assert_eq!(dynamic_intercept.p_value, intercept.p_value);
+        let errors = dynamic.prediction_errors(&[0.0, 1.0], 1.0);
+        assert_eq!(errors, (mean_error, prediction_error));
+    }
+
+    #[test]
+    fn band_margins() {
+        // the residuals of y = 0.8 x + 1.4 leave s² = 3.6 / 3 = 1.2; at the mean x = 2 the mean
+        // prediction has variance s² / 5 and a new observation s² / 5 + s²
+        let data = [(0.0, 1.0), (1.0, 3.0), (2.0, 2.0), (3.0, 5.0), (4.0, 4.0)];
+        let jacobian = data.map(|(x, _)| [x, 1.0]);
+        let residuals = data.map(|(x, y)| y - (0.8 * x + 1.4));
+        let covariance = Covariance::new(&jacobian, &residuals).unwrap();
+        assert!((covariance.residual_variance - 1.2).abs() < 1e-12);
+        let (mean_error, prediction_error) = covariance.prediction_errors([2.0, 1.0], 1.0);
+        assert!((mean_error - 0.24f64.sqrt()).abs() < 1e-12);
+        assert!((prediction_error - 1.2).abs() < 1e-12);
+        // t quantile of 3 degrees of freedom for a 95% interval
+        let t = 3.182_446_305_284_263;
+        let confidence = covariance.margin(mean_error, 0.95);
+        assert!((confidence - t * 0.24f64.sqrt()).abs() < 1e-9);
+        assert!((covariance.margin(prediction_error, 0.95) - t * 1.2).abs() < 1e-9);
+        // bands widen away from the data
+        let (far_error, _) = covariance.prediction_errors([10.0, 1.0], 1.0);
+        assert!(covariance.margin(far_error, 0.95) > 3.0 * confidence);
+    }
+
+    #[test]
+    fn weighted_prediction() {
+        // points of y = 2 x + 1 measured with σ = 0.01, weighted by 1 / σ², scatter by about σ,
+        // so a new observation there is as uncertain as those rather than as the weighted
+        // residuals, which have no units
+        let sigma = 0.01;
+        let data = (0..30).map(|i| {
+            let x = i as f64 / 3.0;
+            let noise = if i % 2 == 0 { sigma } else { -sigma };
+            (x, 2.0 * x + 1.0 + noise)
+        });
+        let (jacobian, residuals): (Vec<_>, Vec<_>) = data
+            .map(|(x, y)| ([x / sigma, 1.0 / sigma], (y - (2.0 * x + 1.0)) / sigma))
+            .unzip();
+        let covariance = Covariance::new(&jacobian, &residuals).unwrap();
+        let weight = 1.0 / (sigma * sigma);
+        let (mean_error, prediction_error) = covariance.prediction_errors([5.0, 1.0], weight);
+        assert!(mean_error < prediction_error);
+        let width = 2.0 * covariance.margin(prediction_error, 0.95);
+        assert!(width > 2.0 * sigma && width < 6.0 * sigma, "{width}");
+    }
+
+    #[test]
+    fn needs_degrees_of_freedom() {
+        // a line through two points fits them exactly, leaving nothing to estimate the variance
+        let jacobian = [[0.0, 1.0], [1.0, 1.0]];
+        assert!(Covariance::new(&jacobian, &[0.0, 0.0]).is_none());
+        assert!(Covariance::new(&jacobian[..1], &[0.5]).is_none());
+        assert!(Covariance::new(&[[1.0], [2.0]], &[0.1, f64::NAN]).is_none());
+        assert!(Covariance::new(&[[1.0], [2.0]], &[0.1, -0.1]).is_some());
+    }
+}